@@ -1,4 +1,4 @@
-use clap::{App, AppSettings, Arg, SubCommand};
+use clap::{App, AppSettings, Arg, ArgGroup, SubCommand};
 
 pub fn build_cli() -> App<'static, 'static> {
     App::new("reti")
@@ -17,19 +17,84 @@ pub fn build_cli() -> App<'static, 'static> {
                         "<storage_file> 'Which file shall be written!'
                         [legacy_file] 'import data from the legacy file!'"))
         .subcommand(SubCommand::with_name("import")
-                    .about("Import from legacy representation. Parts with intersecting times will be disregarded.")
+                    .about("Import from legacy representation. Days with intersecting times will be disregarded.")
                     .args_from_usage("<legacy_file> 'file with legacy format!'"))
         .subcommand(SubCommand::with_name("get")
                     .about("Gets attributes for the current store.")
                     .subcommand(SubCommand::with_name("fee")
                                 .about("Gets the fee per hour.")
                                 .args_from_usage(""))
+                    .subcommand(SubCommand::with_name("rate")
+                                .about("Gets the default fee per hour, all rates for projects and factors, \
+                                       where earned amounts are rounded and how worked time is billed."))
+                    .subcommand(SubCommand::with_name("tax")
+                                .about("Gets the tax (VAT) rates for all parts and per project."))
+                    .subcommand(SubCommand::with_name("target")
+                                .about("Gets the expected working time per weekday."))
+                    .subcommand(SubCommand::with_name("vacation")
+                                .about("Gets the vacation allowance and the remaining vacation days per year."))
                     )
         .subcommand(SubCommand::with_name("set")
                     .about("Sets attributes for the current store.")
                     .subcommand(SubCommand::with_name("fee")
-                                .about("Sets the fee per hour which is the base for all parts!")
-                                .args_from_usage("<value> 'The fee value as f32.'"))
+                                .about("Sets the fee per hour which is the base for all parts! Without \
+                                       a date, the fee applies to all days before the first dated fee.")
+                                .args_from_usage(
+                                    "--from [date] 'The fee is valid from this date (YYYY-MM-DD) on'
+                                    --currency [currency] 'The currency (ISO 4217 code, e.g. EUR) of all fees'
+                                    <value> 'The fee value, e.g. 80.50'"))
+                    .subcommand(SubCommand::with_name("rate")
+                                .about("Sets the fee per hour for a project and/or factor. A rate for a factor \
+                                       is absolute, otherwise the factor of a part is applied to the rate.")
+                                .args_from_usage(
+                                    "-P, --project [project] 'The project (client) the rate applies to'
+                                    --factor [factor] 'The factor the rate applies to'
+                                    --currency [currency] 'The currency (ISO 4217 code, e.g. USD) of the rate (default: the currency of the fee)'
+                                    --remove 'Removes the rate instead of setting it'
+                                    [value] 'The fee value, e.g. 80.50'"
+                                    )
+                                .group(ArgGroup::with_name("fee")
+                                       .args(&["value", "remove"])
+                                       .required(true)))
+                    .subcommand(SubCommand::with_name("tax")
+                                .about("Sets the tax (VAT) rate in percent charged on earned amounts, for all \
+                                       parts or a project. Without a date, the rate applies to all days before \
+                                       the first dated rate.")
+                                .args_from_usage(
+                                    "-P, --project [project] 'The project (client) the rate applies to'
+                                    --from [date] 'The rate is valid from this date (YYYY-MM-DD) on'
+                                    --remove 'Removes the rate instead of setting it'
+                                    [percent] 'The tax rate in percent, e.g. 19'"
+                                    )
+                                .group(ArgGroup::with_name("rate")
+                                       .args(&["percent", "remove"])
+                                       .required(true)))
+                    .subcommand(SubCommand::with_name("rounding")
+                                .about("Sets where earned amounts are rounded to cents: per part (default), \
+                                       per day or only the shown totals.")
+                                .arg(Arg::from_usage("<level> 'The rounding level'")
+                                     .possible_values(&["part", "day", "total"])))
+                    .subcommand(SubCommand::with_name("billing")
+                                .about("Sets how worked time is billed, e.g. in 15 minute increments. The recorded \
+                                       times are not changed. An increment and minimum of 0 bill the exact time.")
+                                .args_from_usage(
+                                    "--minimum [minutes] 'The minimum billable minutes of a part or day'
+                                    <increment> 'The increment in minutes, e.g. 15 or 6'")
+                                .arg(Arg::from_usage("--round [mode] 'Round up, down or to the nearest increment (default: up)'")
+                                     .possible_values(&["up", "down", "nearest"]))
+                                .arg(Arg::from_usage("--per [level] 'Round each part or the whole day (default: part)'")
+                                     .possible_values(&["part", "day"])))
+                    .subcommand(SubCommand::with_name("vacation")
+                                .about("Sets the vacation allowance in days per year.")
+                                .args_from_usage("<days> 'The number of vacation days per year'"))
+                    .subcommand(SubCommand::with_name("target")
+                                .about("Sets the expected working time (in hours) per day, the overtime \
+                                       balance is calculated against.")
+                                .args_from_usage(
+                                    "--weekly 'The hours are per week and spread over the days'
+                                    --days [days] 'Comma separated weekdays, e.g. mon,tue (default: mon-fri)'
+                                    --from [date] 'The target is valid from this date (YYYY-MM-DD) on (default: first recorded day)'
+                                    <hours> 'The hours as f32.'"))
                     )
         .subcommand(SubCommand::with_name("rm")
                     .about("Removes given days from the current store")
@@ -45,13 +110,15 @@ pub fn build_cli() -> App<'static, 'static> {
                                        therefore 'stop' is optional. If only the \
                                        stop should be recorded use '_' for start")
                                 .args_from_usage(
-                                    "<start> 'The format is: HH:MM (default: now), use _ if only stop shall be recorded!'
+                                    "-P, --project [project] 'The project (client) the part belongs to'
+                                    <start> 'The format is: HH:MM (default: now), use _ if only stop shall be recorded!'
                                     [stop] 'Format: HH:MM, optional hence only start will be recorded'"
                                     ))
                     .subcommand(SubCommand::with_name("parts")
                                 .about("Add a parts of the day.")
                                 .args_from_usage(
-                                    "<parts>... 'The format is: HH:MM-HH:MM[-factor] (START-STOP[-FACTOR]).'"
+                                    "-P, --project [project] 'The project (client) for all parts which do not specify one'
+                                    <parts>... 'The format is: HH:MM-HH:MM[-factor][@project] (START-STOP[-FACTOR][@PROJECT]).'"
                                     ))
                     .subcommand(SubCommand::with_name("parse")
                                 .about("Lets you add entries based on free text, either as parameter or via stdin.")
@@ -59,6 +126,23 @@ pub fn build_cli() -> App<'static, 'static> {
                                     "<data>... 'The data that will be attempted to be parsed and added to the store.'"
                                     ))
                     )
+        .subcommand(SubCommand::with_name("start")
+                    .about("Starts a new part for the current day (punch in). Only one part can be running at a time.")
+                    .args_from_usage(
+                        "--factor [factor] 'The factor of the part (default: 1)'
+                        -P, --project [project] 'The project (client) the part belongs to'
+                        --comment [comment] 'Comment for the day, only set if the day has none yet'
+                        [at] 'Format: HH:MM (default: now)'"
+                        ))
+        .subcommand(SubCommand::with_name("stop")
+                    .about("Stops the running part (punch out).")
+                    .args_from_usage(
+                        "[at] 'Format: HH:MM (default: now)'"
+                        ))
+        .subcommand(SubCommand::with_name("status")
+                    .about("Shows the running part and the time elapsed since it has been started."))
+        .subcommand(SubCommand::with_name("balance")
+                    .about("Shows the overtime balance as of today, see 'set target'."))
         .subcommand(SubCommand::with_name("edit")
                     .about("Edit a specific day.").
                     args_from_usage("[dates]... 'can have the format: [yyyy-][mm-]dd, \
@@ -70,8 +154,11 @@ pub fn build_cli() -> App<'static, 'static> {
                         -d, --days 'Show all days'
                         -b, --breaks 'Shows time of breaks'
                         -p. --parts 'Show all parts a of a day'
+                        -P, --project [project] 'Only show parts of the given project (client)'
                         -v, --verbose"
                         )
+                    .arg(Arg::from_usage("--format [format] 'Output format (default: text)'")
+                         .possible_values(&["text", "json", "csv"]))
                     .subcommand(SubCommand::with_name("year")
                                 .args_from_usage(
                                     "[years]... 'Space separated list of years to show (default: current)'"
@@ -83,15 +170,45 @@ pub fn build_cli() -> App<'static, 'static> {
                                     ))
                     .subcommand(SubCommand::with_name("week")
                                 .args_from_usage(
-                                    "-y, --year [year] 'Specify a year (default: current)'
-                                    [weeks]... 'Space separated list of weeks to show (default: current)'"
+                                    "-y, --year [year] 'Specify an ISO year (default: current)'
+                                    [weeks]... 'Space separated list of ISO weeks to show, e.g. 35 or 2025-W01 (default: current)'"
                                     ))
                     .subcommand(SubCommand::with_name("day")
                                 .args_from_usage(
                                     "-y, --year [year] 'Specify a year (default: current)'
                                     -m, --month [month] 'Specify a month (default: current)'
                                     [days]... 'Space separated list of days to show (default: today)'"
+                                    ))
+                    .subcommand(SubCommand::with_name("quarter")
+                                .args_from_usage(
+                                    "-y, --year [year] 'Specify a year (default: current)'
+                                    [quarters]... 'Space separated list of quarters (1-4) to show (default: current)'"
+                                    ))
+                    .subcommand(SubCommand::with_name("fiscal-year")
+                                .about("Shows a fiscal year, named after the year it starts in, see fiscal-year-start in reti.toml")
+                                .args_from_usage(
+                                    "[years]... 'Space separated list of fiscal years to show (default: current)'"
+                                    ))
+                    .subcommand(SubCommand::with_name("range")
+                                .about("Shows the days from one date to another, e.g. an invoicing period")
+                                .args_from_usage(
+                                    "<from> 'First day (YYYY-MM-DD) or a relative period: today, yesterday, this-week, last-week, this-month, last-month, this-quarter, last-quarter, this-year, last-year'
+                                    [to] 'Last day (YYYY-MM-DD) or a relative period (default: <from>)'"
                                     )))
+        .subcommand(SubCommand::with_name("invoice")
+                    .about("Creates an invoice for the parts from one date to another, invoices are numbered sequentially.")
+                    .args_from_usage(
+                        "-P, --project [project] 'Only invoice the parts of this project (client)'
+                        --from <from> 'First day (YYYY-MM-DD) or a relative period, e.g. last-month'
+                        --to [to] 'Last day (YYYY-MM-DD) or a relative period (default: --from)'
+                        --tax [percent] 'The tax rate in percent of all items (default: the configured rates)'
+                        --date [date] 'The date of the invoice (YYYY-MM-DD) (default: today)'
+                        --template [file] 'A template to render instead of the built-in template of the format'
+                        --preview 'Renders the invoice without using up an invoice number'")
+                    .arg(Arg::from_usage("--by [grouping] 'Line items per day or per project and factor (default: day)'")
+                         .possible_values(&["day", "factor"]))
+                    .arg(Arg::from_usage("--format [format] 'Output format (default: text)'")
+                         .possible_values(&["markdown", "html", "text"])))
         .subcommand(SubCommand::with_name("completions")
             .about("Generates completion scripts for your shell")
             .setting(AppSettings::Hidden)
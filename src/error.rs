@@ -0,0 +1,129 @@
+use crate::storage::data::Part;
+use crate::storage::legacy_parser::ParserError;
+use chrono::{NaiveDate, NaiveTime};
+use std::{error, fmt, io};
+
+/// Errors returned by the storage, the caller decides how to report them.
+#[derive(Debug)]
+pub enum Error {
+    /// The part clashes with an existing part of the day.
+    Overlap {
+        date: NaiveDate,
+        part: Part,
+    },
+    StopBeforeStart {
+        start: NaiveTime,
+        stop: NaiveTime,
+    },
+    /// A part stopping on the next day would last longer than 24h.
+    TooLong {
+        start: NaiveTime,
+        stop: NaiveTime,
+    },
+    NoParts(NaiveDate),
+    DateMismatch(NaiveDate, NaiveDate),
+    /// A part is running since the given date and time.
+    AlreadyRunning(NaiveDate, NaiveTime),
+    AlreadyStopped,
+    NotRunning,
+    MultipleRunning,
+    RunningOnOtherDay(NaiveDate),
+    /// The given line (starting at 1) of a legacy file can't be parsed.
+    Parse {
+        line: usize,
+        error: ParserError,
+    },
+    /// The store has been written by a newer version of reti: (store, supported) version.
+    UnsupportedVersion(u32, u32),
+    /// The holiday calendar can't be read.
+    Calendar(String),
+    UnknownTimeZone(String),
+    /// There are no worked parts in the range to invoice.
+    NothingToInvoice(NaiveDate, NaiveDate),
+    /// The parts to invoice are charged in the given (different) currencies.
+    MixedCurrencies(Vec<String>),
+    Io(io::Error),
+    Decode(serde_json::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Overlap { date, part } => {
+                write!(f, "Part {} clashes with an existing part on {}", part, date)
+            }
+            Error::StopBeforeStart { start, stop } => write!(
+                f,
+                "Well, did you stop working ({}) before you started ({})?",
+                stop, start
+            ),
+            Error::TooLong { start, stop } => write!(
+                f,
+                "A part can't last longer than a day ({}-{}+1)",
+                start.format("%H:%M"),
+                stop.format("%H:%M")
+            ),
+            Error::NoParts(date) => write!(f, "No parts specified for {}", date),
+            Error::DateMismatch(a, b) => write!(f, "Not the same day: {} - {}", a, b),
+            Error::AlreadyRunning(date, start) => write!(
+                f,
+                "There is already a running part since {} {}",
+                date,
+                start.format("%H:%M")
+            ),
+            Error::AlreadyStopped => write!(f, "The part to start has already been stopped"),
+            Error::NotRunning => write!(f, "There is no running part to stop"),
+            Error::MultipleRunning => write!(
+                f,
+                "There is more than one running part, use edit to fix the store"
+            ),
+            Error::RunningOnOtherDay(date) => write!(
+                f,
+                "The running part has been started on {}, use edit to stop it",
+                date
+            ),
+            Error::Parse { line, error } => {
+                write!(f, "Unable to parse line {}: {:?}", line, error)
+            }
+            Error::UnsupportedVersion(v, supported) => write!(
+                f,
+                "Store version {} is newer than the supported version {}",
+                v, supported
+            ),
+            Error::Calendar(e) => write!(f, "Invalid holiday calendar: {}", e),
+            Error::UnknownTimeZone(name) => write!(f, "Unknown time zone: {}", name),
+            Error::NothingToInvoice(from, to) => {
+                write!(f, "Nothing to invoice from {} to {}", from, to)
+            }
+            Error::MixedCurrencies(codes) => write!(
+                f,
+                "An invoice can't mix currencies ({}), select a project",
+                codes.join(", ")
+            ),
+            Error::Io(e) => write!(f, "{}", e),
+            Error::Decode(e) => write!(f, "Unable to decode store: {}", e),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            Error::Decode(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Error {
+        Error::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Error {
+        Error::Decode(e)
+    }
+}
@@ -0,0 +1,21 @@
+//! reti records periods of worked time per day and stores them in a json file (store).
+//!
+//! The `reti` binary is a thin CLI on top of this crate, the same types can be used to read a
+//! store and report on it without going through `reti show`:
+//!
+//! ```no_run
+//! use reti::storage::data::Storage;
+//! use reti::Printer;
+//!
+//! let store = Storage::from_file("store.json").unwrap();
+//! if let Some(year) = store.get_year(2017) {
+//!     println!("{}", Printer::with_years(vec![year]).show_worked(true));
+//! }
+//! ```
+
+mod error;
+pub mod printing;
+pub mod storage;
+
+pub use crate::error::Error;
+pub use crate::printing::printer::Printer;
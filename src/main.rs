@@ -5,23 +5,29 @@ extern crate clap;
 extern crate failure;
 
 mod cli;
-mod printing;
-mod storage;
 mod utils;
 
-use crate::printing::printer;
-use crate::storage::data;
-use crate::storage::legacy_parser;
 use chrono::*;
 use clap::{ArgMatches, Shell};
-use failure::Error;
+use reti::printing::invoice::{Grouping, Invoice, InvoiceFormat};
+use reti::printing::report::{Format, Report};
+use reti::printing::{csv, json, printer};
+use reti::storage::currency::Currency;
+use reti::storage::data;
+use reti::storage::decimal::Decimal;
+use reti::storage::holidays::Calendar;
+use reti::storage::legacy_parser;
+use reti::storage::lock;
+use reti::storage::zone::Zone;
+use reti::Error;
+use std::collections::HashMap;
 use std::env;
 use std::io;
 use std::io::prelude::*;
 use std::io::BufReader;
 use std::process::{exit, Command};
 
-fn get_settings() -> Result<config::Config, Error> {
+fn get_settings() -> Result<config::Config, failure::Error> {
     let xdg_dirs = xdg::BaseDirectories::with_prefix("reti")?;
     let config_path = match xdg_dirs.find_config_file("reti.toml") {
         Some(x) => x,
@@ -37,7 +43,7 @@ fn get_settings() -> Result<config::Config, Error> {
 fn main() {
     let args = cli::build_cli().get_matches();
 
-    if let Some(ref matches) = args.subcommand_matches("completions") {
+    if let Some(matches) = args.subcommand_matches("completions") {
         let shell = matches.value_of("SHELL").unwrap();
         cli::build_cli().gen_completions_to(
             "reti",
@@ -48,11 +54,11 @@ fn main() {
     }
 
     let mut pretty_json = args.is_present("save-pretty");
-
-    if let Some(ref matches) = args.subcommand_matches("init") {
-        subcmd_init(matches, pretty_json);
-        return;
-    }
+    let mut backups = 0;
+    let mut break_rules = data::BreakRule::defaults();
+    let mut holidays = Calendar::default();
+    let mut zone = Zone::from_env();
+    let mut fiscal_year_start = 1;
 
     let mut storage_file = String::new();
     if let Ok(settings) = get_settings() {
@@ -62,44 +68,110 @@ fn main() {
         if let Ok(p) = settings.get_bool("save-pretty") {
             pretty_json = p;
         }
+        if let Ok(b) = settings.get_int("backups") {
+            backups = b.max(0) as usize;
+        }
+        if let Ok(r) = settings.get::<Vec<data::BreakRule>>("break-rules") {
+            break_rules = r;
+        }
+        if let Ok(file) = settings.get_str("holidays") {
+            let region = settings.get_str("holiday-region").ok();
+            match Calendar::from_file(&file) {
+                Ok(c) => holidays = c.for_region(region.as_deref()),
+                Err(e) => eprintln!("Unable to load holidays from {}: {}", file, e),
+            }
+        }
+        if let Ok(m) = settings.get_int("fiscal-year-start") {
+            match m {
+                1..=12 => fiscal_year_start = m as u32,
+                _ => eprintln!("Invalid fiscal-year-start {}, using January", m),
+            }
+        }
+        if let Ok(name) = settings.get_str("timezone") {
+            match name.parse() {
+                Ok(z) => zone = z,
+                Err(e) => eprintln!("{}, using {:?}", e, zone),
+            }
+        }
+    }
+
+    if let Some(matches) = args.subcommand_matches("init") {
+        subcmd_init(matches, pretty_json, backups);
+        return;
     }
 
     if let Ok(f) = value_t!(args, "file", String) {
         storage_file = f;
     }
-    println!("Use storage_file: {}", storage_file);
+    eprintln!("Use storage_file: {}", storage_file);
+
+    let mutating = ["import", "set", "rm", "add", "start", "stop", "invoice"]
+        .iter()
+        .any(|c| args.subcommand_matches(c).is_some());
+    let mut store_lock = if mutating {
+        Some(acquire_lock(&storage_file))
+    } else {
+        None
+    };
+    let loaded = lock::fingerprint(&storage_file).ok();
 
     let mut store = match data::Storage::from_file(&storage_file) {
         Ok(store) => store,
         Err(e) => {
-            println!("{:?}", e);
+            eprintln!("Unable to open {}: {}", storage_file, e);
             exit(-1);
         }
     };
 
-    if let Some(ref matches) = args.subcommand_matches("show") {
-        subcmd_show(&store, matches);
+    let mut do_write = false;
+    if let Some(version) = store.upgraded_from() {
+        eprintln!(
+            "Store has been upgraded from version {} to {}",
+            version,
+            data::STORE_VERSION
+        );
+        do_write = true;
     }
 
-    let mut do_write = false;
-    if let Some(ref matches) = args.subcommand_matches("import") {
+    if let Some(matches) = args.subcommand_matches("show") {
+        match matches.value_of("project") {
+            Some(project) => subcmd_show(
+                &store.with_project(project),
+                matches,
+                &break_rules,
+                &holidays,
+                &zone,
+                fiscal_year_start,
+            ),
+            None => subcmd_show(
+                &store,
+                matches,
+                &break_rules,
+                &holidays,
+                &zone,
+                fiscal_year_start,
+            ),
+        }
+    }
+
+    if let Some(matches) = args.subcommand_matches("import") {
         subcmd_import(&mut store, matches);
         do_write = true;
     }
 
-    if let Some(ref matches) = args.subcommand_matches("get") {
-        subcmd_get(&store, matches)
+    if let Some(matches) = args.subcommand_matches("get") {
+        subcmd_get(&store, matches, &zone)
     }
 
-    if let Some(ref matches) = args.subcommand_matches("set") {
-        if subcmd_set(&mut store, matches) {
+    if let Some(matches) = args.subcommand_matches("set") {
+        if subcmd_set(&mut store, matches, &zone) {
             do_write = true;
         } else {
             println!("Setting did not succeed, nothing will be saved!");
         }
     }
 
-    if let Some(ref matches) = args.subcommand_matches("rm") {
+    if let Some(matches) = args.subcommand_matches("rm") {
         if subcmd_remove(&mut store, matches) {
             do_write = true;
         } else {
@@ -107,52 +179,115 @@ fn main() {
         }
     }
 
-    if let Some(ref matches) = args.subcommand_matches("add") {
-        if subcmd_add(&mut store, matches) {
+    if let Some(matches) = args.subcommand_matches("add") {
+        if subcmd_add(&mut store, matches, &zone) {
             do_write = true;
         } else {
             println!("Add did not work, nothing will be saved!");
         }
     }
 
-    if let Some(ref matches) = args.subcommand_matches("edit") {
-        if subcmd_edit(&mut store, matches) {
+    if let Some(matches) = args.subcommand_matches("start") {
+        if subcmd_start(&mut store, matches, &zone) {
             do_write = true;
         } else {
-            println!("Edit canceled, nothing will be saved!");
+            println!("Start did not work, nothing will be saved!");
+        }
+    }
+
+    if let Some(matches) = args.subcommand_matches("stop") {
+        if subcmd_stop(&mut store, matches, &zone) {
+            do_write = true;
+        } else {
+            println!("Stop did not work, nothing will be saved!");
+        }
+    }
+
+    if args.subcommand_matches("status").is_some() {
+        subcmd_status(&store, &zone);
+    }
+
+    if args.subcommand_matches("balance").is_some() {
+        subcmd_balance(&store, &holidays, &zone);
+    }
+
+    if let Some(matches) = args.subcommand_matches("invoice") {
+        if subcmd_invoice(&mut store, matches, &zone) {
+            do_write = true;
+        }
+    }
+
+    if let Some(matches) = args.subcommand_matches("edit") {
+        match subcmd_edit(&mut store, matches, &storage_file, loaded, &zone) {
+            Some(l) => {
+                store_lock = Some(l);
+                do_write = true;
+            }
+            None => println!("Edit canceled, nothing will be saved!"),
+        }
+    }
+
+    if do_write && store_lock.is_none() {
+        // only the upgrade of the store is pending: neither wait for nor clobber others
+        store_lock = lock::StoreLock::try_acquire(&storage_file).ok().flatten();
+        if store_lock.is_none() || lock::fingerprint(&storage_file).ok() != loaded {
+            eprintln!("Store is in use, the upgrade will be saved next time.");
+            do_write = false;
+        }
+    }
+
+    if do_write {
+        if let Err(e) = store.save(&storage_file, pretty_json, backups) {
+            eprintln!("Unable to write file {}: {}", &storage_file, e);
         }
     }
+}
 
-    if do_write && !store.save(&storage_file, pretty_json) {
-        println!("Unable to write file: {}", &storage_file);
+/// Prints the error, if any, and returns whether the result has been a success.
+fn succeeded(result: Result<(), Error>) -> bool {
+    match result {
+        Ok(()) => true,
+        Err(e) => {
+            println!("{}", e);
+            false
+        }
+    }
+}
+
+fn import_legacy(store: &mut data::Storage, leg_file: &str) {
+    match store.import_legacy(leg_file) {
+        Ok(skipped) => {
+            for e in skipped {
+                println!("Skipped: {}", e);
+            }
+        }
+        Err(e) => println!("Unable to import data: {}", e),
     }
 }
 
 fn subcmd_import(store: &mut data::Storage, matches: &ArgMatches) {
     let leg_file = value_t!(matches, "legacy_file", String).unwrap_or_else(|e| e.exit());
 
-    if !store.import_legacy(&leg_file) {
-        println!("Unable to import data!")
-    }
+    import_legacy(store, &leg_file);
 }
 
 fn subcmd_remove(store: &mut data::Storage, matches: &ArgMatches) -> bool {
     let dates = values_t!(matches, "dates", String).unwrap_or_else(|_| vec![]);
 
     let force = matches.is_present("force");
-    let dates = dates.iter().filter_map(|d| legacy_parser::parse_date(&d));
+    let dates = dates.iter().filter_map(|d| legacy_parser::parse_date(d));
 
     let mut removed = false;
 
     for date in dates {
         if !force {
             let q = format!("Really remove {} from store? [y/N] ", date);
-            match utils::yes_no(q.as_ref(), utils::YesNoAnswer::NO) {
-                utils::YesNoAnswer::NO => {
+            match utils::yes_no(q.as_ref(), utils::YesNoAnswer::No) {
+                utils::YesNoAnswer::No => {
                     println!("Skip removal of {}.", date);
                     continue;
                 }
-                utils::YesNoAnswer::YES => (),
+                utils::YesNoAnswer::Yes => (),
             }
         }
 
@@ -166,29 +301,66 @@ fn subcmd_remove(store: &mut data::Storage, matches: &ArgMatches) -> bool {
     removed
 }
 
-fn subcmd_edit(store: &mut data::Storage, matches: &ArgMatches) -> bool {
+fn acquire_lock(storage_file: &str) -> lock::StoreLock {
+    let lock = match lock::StoreLock::try_acquire(storage_file) {
+        Ok(Some(l)) => Ok(l),
+        Ok(None) => {
+            eprintln!("Waiting for the lock on {} ...", storage_file);
+            lock::StoreLock::acquire(storage_file)
+        }
+        Err(e) => Err(e),
+    };
+    match lock {
+        Ok(l) => l,
+        Err(e) => {
+            println!("Unable to lock {}: {}", storage_file, e);
+            exit(-1);
+        }
+    }
+}
+
+/// Lets the user edit days in $EDITOR. The store is not locked while editing, hence the store
+/// will be locked afterwards and checked for changes made since it has been `loaded`. Only the
+/// days which have been changed in the editor are applied, and only if they have not been
+/// changed in the store meanwhile. On success, the lock is returned and has to be held until
+/// the store has been saved.
+fn subcmd_edit(
+    store: &mut data::Storage,
+    matches: &ArgMatches,
+    storage_file: &str,
+    loaded: Option<u64>,
+    zone: &Zone,
+) -> Option<lock::StoreLock> {
     let p_dates = values_t!(matches, "dates", String).unwrap_or_else(|_| vec![]);
 
     let dates = p_dates
         .iter()
-        .filter_map(|d| legacy_parser::parse_date(&d))
-        .filter_map(|d| store.get_day(d.year() as u16, d.month() as u8, d.day() as u8))
-        .map(|d| d.as_legacy())
-        .collect::<Vec<String>>();
+        .filter_map(|d| legacy_parser::parse_date(d))
+        .filter_map(|d| store.get_day_nd(d))
+        .map(|d| (d.date, d.as_legacy()))
+        .collect::<Vec<(NaiveDate, String)>>();
+    // the days as shown in the editor, to tell which of them have been changed
+    let mut shown = dates
+        .iter()
+        .cloned()
+        .collect::<HashMap<NaiveDate, String>>();
+    let dates = dates.into_iter().map(|(_, l)| l).collect::<Vec<String>>();
 
     let mut s = dates.join("\n");
     if s.is_empty() {
-        let today = Utc::today().naive_local();
-        if let Some(day) =
-            store.get_day(today.year() as u16, today.month() as u8, today.day() as u8)
-        {
+        let today = zone.today();
+        if let Some(day) = store.get_day_nd(today) {
+            shown.insert(today, day.as_legacy());
             s.push_str(&day.as_legacy())
         } else {
             s.push_str("# Lines starting with '#' will be ignored\n");
             s.push_str("# Default date is today!\n");
-            s.push_str("# Date         Parts w/o and w/ factor (0.5)  Comment\n");
-            s.push_str("# 2016-04-25   08:00-12:00  13:00-17:00-0.5   # coment\n");
-            let today = data::Day::new_today();
+            s.push_str("# Date         Parts w/o and w/ factor (0.5) and project  Comment\n");
+            s.push_str("# 2016-04-25   08:00-12:00  13:00-17:00-0.5@acme   # coment\n");
+            s.push_str("# Absence (vacation, sick, holiday or comp-time) with optional parts\n");
+            s.push_str("# 2016-04-26   !vacation   # summer\n");
+            let today = data::Day::new_today(zone);
+            shown.insert(today.date, today.as_legacy());
             s.push_str(&today.as_legacy())
         }
     }
@@ -202,12 +374,12 @@ fn subcmd_edit(store: &mut data::Storage, matches: &ArgMatches) -> bool {
     {
         Err(e) => {
             println!("Error occured: '{:?}'", e);
-            return false;
+            return None;
         }
         Ok(x) => {
             if !x.success() {
                 println!("Editor exit was failure!");
-                return false;
+                return None;
             }
         }
     }
@@ -215,6 +387,7 @@ fn subcmd_edit(store: &mut data::Storage, matches: &ArgMatches) -> bool {
     let mut f = BufReader::new(file);
     let _ = f.seek(std::io::SeekFrom::Start(0));
 
+    let mut days = vec![];
     for (i, line) in f.lines().enumerate() {
         let line = match line {
             Ok(l) => l,
@@ -225,37 +398,311 @@ fn subcmd_edit(store: &mut data::Storage, matches: &ArgMatches) -> bool {
             continue;
         }
 
-        let day = match legacy_parser::parse_line(&line) {
+        let mut day = match legacy_parser::parse_line(&line) {
             Ok(day) => day,
             Err(e) => {
                 println!("ignore {}: '{}' {:?}", i, line, e);
                 continue;
             }
         };
+        if shown.get(&day.date) == Some(&day.as_legacy()) {
+            continue;
+        }
 
-        store.add_day_force(day);
+        if let Some(stored) = store.get_day_nd(day.date) {
+            day.keep_offsets(stored);
+        }
+        for part in &mut day.parts {
+            zone.resolve(day.date, part);
+        }
+        days.push(day);
     }
 
-    true
+    let store_lock = acquire_lock(storage_file);
+    if lock::fingerprint(storage_file).ok() != loaded {
+        let q = "The store has been changed since it was opened. \
+                 Apply the days you edited to it? Otherwise your edits are discarded. [y/N]";
+        match utils::yes_no(q, utils::YesNoAnswer::No) {
+            utils::YesNoAnswer::No => return None,
+            utils::YesNoAnswer::Yes => (),
+        }
+        let reloaded = match data::Storage::from_file(storage_file) {
+            Ok(s) => s,
+            Err(e) => {
+                println!("Unable to reload {}: {}", storage_file, e);
+                return None;
+            }
+        };
+        let opened = std::mem::replace(store, reloaded);
+        for day in &days {
+            if opened.get_day_nd(day.date) != store.get_day_nd(day.date) {
+                println!(
+                    "{} has been changed in the store as well, unable to apply your edits!",
+                    day.date
+                );
+                return None;
+            }
+        }
+    }
+
+    for day in days {
+        succeeded(store.add_day_force(day));
+    }
+
+    Some(store_lock)
 }
 
-fn subcmd_get(store: &data::Storage, matches: &ArgMatches) {
-    if let Some(ref _matches) = matches.subcommand_matches("fee") {
-        println!("Current fee: {}", store.get_fee());
+fn subcmd_get(store: &data::Storage, matches: &ArgMatches, zone: &Zone) {
+    if matches.subcommand_matches("fee").is_some() {
+        let rates = store.get_rates();
+        let fee = |x| printer::fmt_fee(x, rates.currency.as_ref());
+        println!("Current fee: {}", fee(rates.default_at(zone.today())));
+        if !rates.history.is_empty() {
+            println!("Base fee: {}", fee(rates.default));
+            for x in &rates.history {
+                println!("Fee from {}: {}", x.effective_from, fee(x.fee));
+            }
+        }
+    }
+
+    if matches.subcommand_matches("rate").is_some() {
+        let rates = store.get_rates();
+        let currency = rates.currency.as_ref();
+        println!("Default fee: {}", printer::fmt_fee(rates.default, currency));
+        for r in &rates.overrides {
+            let fee = printer::fmt_fee(r.fee, r.currency.as_ref().or(currency));
+            println!("Fee {}: {}", printer::fmt_rate_key(r), fee);
+        }
+        println!("Rounding: {}", rates.rounding);
+        if let Some(b) = rates.billing {
+            println!("Billing: {}", b);
+        }
+    }
+
+    if matches.subcommand_matches("tax").is_some() {
+        let rates = store.get_rates();
+        if rates.taxes.is_empty() {
+            println!("No tax rate has been set.");
+        }
+        for t in &rates.taxes {
+            let mut key = String::new();
+            if let Some(ref p) = t.project {
+                key.push_str(&format!(" project {}", p));
+            }
+            if let Some(from) = t.effective_from {
+                key.push_str(&format!(" from {}", from));
+            }
+            println!("Tax{}: {}%", key, t.rate);
+        }
+    }
+
+    if matches.subcommand_matches("vacation").is_some() {
+        match store.get_vacation_days() {
+            Some(allowance) => {
+                println!("Vacation allowance: {} day(s) per year", allowance);
+                for y in store.get_years() {
+                    let taken = y.absences(data::Absence::Vacation);
+                    println!(
+                        "  {}: {} taken, {} remaining",
+                        y.year,
+                        taken,
+                        i64::from(allowance) - taken as i64
+                    );
+                }
+            }
+            None => println!("No vacation allowance has been set."),
+        }
+    }
+
+    if matches.subcommand_matches("target").is_some() {
+        match store.get_target() {
+            Some(t) => {
+                println!("Target from {}:", t.effective_from);
+                let days = ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+                for (day, m) in days.iter().zip(t.daily.iter()) {
+                    println!("  {}: {:.2}h", day, *m as f64 / 60.0);
+                }
+            }
+            None => println!("No target has been set."),
+        }
     }
 }
 
-fn subcmd_set(store: &mut data::Storage, matches: &ArgMatches) -> bool {
-    if let Some(ref matches) = matches.subcommand_matches("fee") {
-        let fee = value_t!(matches, "value", f32).unwrap_or_else(|e| e.exit());
-        store.set_fee(fee);
+fn subcmd_set(store: &mut data::Storage, matches: &ArgMatches, zone: &Zone) -> bool {
+    if let Some(matches) = matches.subcommand_matches("fee") {
+        let fee = value_t!(matches, "value", Decimal).unwrap_or_else(|e| e.exit());
+        if matches.is_present("currency") {
+            let currency = value_t!(matches, "currency", Currency).unwrap_or_else(|e| e.exit());
+            store.set_currency(currency);
+        }
+        if let Some(from) = matches.value_of("from") {
+            match legacy_parser::parse_date(from) {
+                Some(from) => store.set_fee_from(from, fee),
+                None => {
+                    println!("Unable to parse date: format YYYY-MM-DD");
+                    return false;
+                }
+            }
+        } else {
+            store.set_current_fee(zone.today(), fee);
+        }
+        return true;
+    }
+
+    if let Some(matches) = matches.subcommand_matches("rate") {
+        let project = value_t!(matches, "project", String).ok();
+        let factor = if matches.is_present("factor") {
+            Some(value_t!(matches, "factor", Decimal).unwrap_or_else(|e| e.exit()))
+        } else {
+            None
+        };
+
+        if matches.is_present("remove") {
+            if !store.remove_rate(project.as_deref(), factor) {
+                println!("No such rate has been set!");
+                return false;
+            }
+            return true;
+        }
+
+        let fee = value_t!(matches, "value", Decimal).unwrap_or_else(|e| e.exit());
+        let currency = if matches.is_present("currency") {
+            Some(value_t!(matches, "currency", Currency).unwrap_or_else(|e| e.exit()))
+        } else {
+            None
+        };
+        if project.is_none() && factor.is_none() {
+            store.set_current_fee(zone.today(), fee);
+            if let Some(currency) = currency {
+                store.set_currency(currency);
+            }
+        } else {
+            store.set_rate(project, factor, fee, currency);
+        }
+        return true;
+    }
+
+    if let Some(matches) = matches.subcommand_matches("tax") {
+        let project = value_t!(matches, "project", String).ok();
+        let from = match matches.value_of("from") {
+            Some(from) => match legacy_parser::parse_date(from) {
+                Some(from) => Some(from),
+                None => {
+                    println!("Unable to parse date: format YYYY-MM-DD");
+                    return false;
+                }
+            },
+            None => None,
+        };
+
+        if matches.is_present("remove") {
+            if !store.remove_tax(project.as_deref(), from) {
+                println!("No such tax rate has been set!");
+                return false;
+            }
+            return true;
+        }
+
+        let rate = value_t!(matches, "percent", Decimal).unwrap_or_else(|e| e.exit());
+        store.set_tax(project, from, rate);
+        return true;
+    }
+
+    if let Some(matches) = matches.subcommand_matches("rounding") {
+        let rounding = value_t!(matches, "level", data::Rounding).unwrap_or_else(|e| e.exit());
+        store.set_rounding(rounding);
+        return true;
+    }
+
+    if let Some(matches) = matches.subcommand_matches("billing") {
+        let minimum = if matches.is_present("minimum") {
+            value_t!(matches, "minimum", u32).unwrap_or_else(|e| e.exit())
+        } else {
+            0
+        };
+        let billing = data::Billing {
+            increment: value_t!(matches, "increment", u32).unwrap_or_else(|e| e.exit()),
+            mode: value_t!(matches, "round", data::RoundingMode).unwrap_or_default(),
+            per: value_t!(matches, "per", data::BillingLevel).unwrap_or_default(),
+            minimum,
+        };
+        if billing.increment == 0 && billing.minimum == 0 {
+            store.set_billing(None);
+        } else {
+            store.set_billing(Some(billing));
+        }
+        return true;
+    }
+
+    if let Some(matches) = matches.subcommand_matches("vacation") {
+        let days = value_t!(matches, "days", u32).unwrap_or_else(|e| e.exit());
+        store.set_vacation_days(days);
+        return true;
+    }
+
+    if let Some(matches) = matches.subcommand_matches("target") {
+        let hours = value_t!(matches, "hours", f32).unwrap_or_else(|e| e.exit());
+        let days = match matches.value_of("days") {
+            Some(days) => match parse_weekdays(days) {
+                Some(days) => days,
+                None => {
+                    println!("Unable to parse days: format mon,tue,...");
+                    return false;
+                }
+            },
+            None => vec![
+                Weekday::Mon,
+                Weekday::Tue,
+                Weekday::Wed,
+                Weekday::Thu,
+                Weekday::Fri,
+            ],
+        };
+        let effective_from = match matches.value_of("from") {
+            Some(from) => match legacy_parser::parse_date(from) {
+                Some(from) => from,
+                None => {
+                    println!("Unable to parse date: format YYYY-MM-DD");
+                    return false;
+                }
+            },
+            None => store.first_day().unwrap_or_else(|| zone.today()),
+        };
+
+        let mut days = days;
+        days.sort_by_key(|d| d.num_days_from_monday());
+        days.dedup();
+        let minutes = (hours * 60.0).round() as i64;
+        let mut daily = [0; 7];
+        for (i, d) in days.iter().enumerate() {
+            daily[d.num_days_from_monday() as usize] = if matches.is_present("weekly") {
+                // the remainder is spread over the first days, the week has the given hours
+                let n = days.len() as i64;
+                minutes / n + i64::from((i as i64) < minutes % n)
+            } else {
+                minutes
+            };
+        }
+        store.set_target(data::Target {
+            effective_from,
+            daily,
+        });
         return true;
     }
     false
 }
 
-fn subcmd_add(store: &mut data::Storage, matches: &ArgMatches) -> bool {
-    if let Some(ref matches) = matches.subcommand_matches("part") {
+/// Parses a comma separated list of weekdays, e.g. "mon,tue,wed".
+fn parse_weekdays(s: &str) -> Option<Vec<Weekday>> {
+    s.split(',')
+        .filter(|x| !x.trim().is_empty())
+        .map(|x| x.trim().parse::<Weekday>().ok())
+        .collect::<Option<Vec<_>>>()
+        .filter(|x| !x.is_empty())
+}
+
+fn subcmd_add(store: &mut data::Storage, matches: &ArgMatches, zone: &Zone) -> bool {
+    if let Some(matches) = matches.subcommand_matches("part") {
         let start = value_t!(matches, "start", String).unwrap_or_else(|e| e.exit());
         let start = legacy_parser::parse_time(&start);
         if start.is_none() {
@@ -267,42 +714,62 @@ fn subcmd_add(store: &mut data::Storage, matches: &ArgMatches) -> bool {
             start,
             stop: None,
             factor: None,
+            project: value_t!(matches, "project", String).ok(),
+            next_day: false,
+            start_offset: None,
+            stop_offset: None,
         };
 
         if let Ok(stop) = value_t!(matches, "stop", String) {
-            part.stop = legacy_parser::parse_time(&stop); // { Some(stop);
+            if let Some((stop, next_day)) = legacy_parser::parse_stop(&stop) {
+                part.stop = Some(stop);
+                part.next_day = next_day;
+            }
         }
 
-        let date = Utc::today().naive_local();
-        return store.add_part(date, part);
+        let date = zone.today();
+        zone.resolve(date, &mut part);
+        return succeeded(store.add_part(date, part));
     }
 
-    if let Some(ref matches) = matches.subcommand_matches("parts") {
+    if let Some(matches) = matches.subcommand_matches("parts") {
         let mut parts = vec![];
         let parts_s = values_t!(matches, "parts", String).unwrap_or_else(|e| e.exit());
+        let project = value_t!(matches, "project", String).ok();
         for part in parts_s {
             match legacy_parser::parse_part(&part) {
-                Some(part) => parts.push(part),
+                Some(mut part) => {
+                    if part.project.is_none() {
+                        part.project = project.clone();
+                    }
+                    parts.push(part)
+                }
                 None => {
                     println!("Unable to parse part: {}", part);
                     return false;
                 }
             }
         }
-        let date = Utc::today().naive_local();
-        for part in parts {
-            if !store.add_part(date, part) {
+        let date = zone.today();
+        for mut part in parts {
+            zone.resolve(date, &mut part);
+            if !succeeded(store.add_part(date, part)) {
                 return false;
             }
         }
         return true;
     }
 
-    if let Some(ref matches) = matches.subcommand_matches("parse") {
+    if let Some(matches) = matches.subcommand_matches("parse") {
         let data = values_t!(matches, "data", String).unwrap_or_else(|e| e.exit());
 
         match legacy_parser::parse_line(&data.join(" ")) {
-            Ok(day) => return store.add_day(day),
+            Ok(mut day) => {
+                for part in &mut day.parts {
+                    zone.resolve(day.date, part);
+                }
+                return succeeded(store.add_day(day));
+            }
             Err(_) => {
                 println!("Unable to parse data");
                 return false;
@@ -312,45 +779,280 @@ fn subcmd_add(store: &mut data::Storage, matches: &ArgMatches) -> bool {
     false
 }
 
-fn subcmd_init(matches: &ArgMatches, pretty: bool) {
+/// Returns the time given as argument `name` or the current time (minute precision) if absent.
+fn value_time_or_now(matches: &ArgMatches, name: &str, now: &NaiveDateTime) -> Option<NaiveTime> {
+    match matches.value_of(name) {
+        Some(t) => legacy_parser::parse_time(t),
+        None => Some(NaiveTime::from_hms(now.hour(), now.minute(), 0)),
+    }
+}
+
+fn subcmd_start(store: &mut data::Storage, matches: &ArgMatches, zone: &Zone) -> bool {
+    let now = zone.now();
+    let start = match value_time_or_now(matches, "at", &now.naive_local()) {
+        Some(t) => t,
+        None => {
+            println!("Unable to parse start as time: format HH:MM");
+            return false;
+        }
+    };
+    let factor = if matches.is_present("factor") {
+        Some(value_t!(matches, "factor", Decimal).unwrap_or_else(|e| e.exit()))
+    } else {
+        None
+    };
+    let mut part = data::Part {
+        start,
+        stop: None,
+        factor,
+        project: value_t!(matches, "project", String).ok(),
+        next_day: false,
+        start_offset: None,
+        stop_offset: None,
+    };
+    let comment = value_t!(matches, "comment", String).ok();
+    let date = now.naive_local().date();
+    if !matches.is_present("at") {
+        part.start_offset = Some(now.offset().local_minus_utc());
+    }
+    zone.resolve(date, &mut part);
+
+    if !succeeded(store.start_part(date, part, comment)) {
+        return false;
+    }
+    println!("Started at {}", start.format("%H:%M"));
+    true
+}
+
+fn subcmd_stop(store: &mut data::Storage, matches: &ArgMatches, zone: &Zone) -> bool {
+    let now = zone.now();
+    let date = now.naive_local().date();
+    let stop = match value_time_or_now(matches, "at", &now.naive_local()) {
+        Some(t) => t,
+        None => {
+            println!("Unable to parse stop as time: format HH:MM");
+            return false;
+        }
+    };
+
+    let offset = if matches.is_present("at") {
+        zone.offset(&date.and_time(stop), false)
+            .map(|x| x.local_minus_utc())
+    } else {
+        Some(now.offset().local_minus_utc())
+    };
+
+    if !succeeded(store.stop_part(date, stop, offset)) {
+        return false;
+    }
+    println!("Stopped at {}", stop.format("%H:%M"));
+    true
+}
+
+fn subcmd_status(store: &data::Storage, zone: &Zone) {
+    let now = zone.now();
+    let open = store.open_parts();
+    if open.is_empty() {
+        println!("No part is running.");
+        return;
+    }
+
+    for (date, part) in open {
+        let start = date.and_time(part.start);
+        let elapsed = match part.start_offset {
+            Some(offset) => now.naive_utc() - (start - Duration::seconds(i64::from(offset))),
+            None => now.naive_local() - start,
+        };
+        println!(
+            "Running since {} {} (factor: {:.1}{}), elapsed: {:.2}h",
+            date,
+            part.start.format("%H:%M"),
+            part.factor.unwrap_or(Decimal::ONE),
+            match part.project {
+                Some(ref p) => format!(", project: {}", p),
+                None => "".to_string(),
+            },
+            elapsed.num_minutes() as f64 / 60.0
+        );
+    }
+}
+
+fn subcmd_balance(store: &data::Storage, holidays: &Calendar, zone: &Zone) {
+    let today = zone.today();
+    match store.balance(holidays, today) {
+        Some(b) => println!(
+            "Overtime balance as of {}: {:+.2}h (worked: {:.2}h, absences: {:.2}h, expected: {:.2}h)",
+            b.until,
+            b.overtime().num_minutes() as f64 / 60.0,
+            b.worked.num_minutes() as f64 / 60.0,
+            b.credited.num_minutes() as f64 / 60.0,
+            b.expected.num_minutes() as f64 / 60.0
+        ),
+        None => println!("No target has been set, see 'reti set target'."),
+    }
+}
+
+fn subcmd_invoice(store: &mut data::Storage, matches: &ArgMatches, zone: &Zone) -> bool {
+    let today = zone.today();
+    let from = value_t!(matches, "from", String).unwrap_or_else(|e| e.exit());
+    let to = value_t!(matches, "to", String).unwrap_or_else(|_| from.clone());
+    let (from, to) = match (
+        legacy_parser::parse_period(&from, today),
+        legacy_parser::parse_period(&to, today),
+    ) {
+        (Some((from, _)), Some((_, to))) => (from, to),
+        _ => {
+            eprintln!("Unable to parse range: format YYYY-MM-DD or e.g. last-month");
+            return false;
+        }
+    };
+    let date = match matches.value_of("date") {
+        Some(date) => match legacy_parser::parse_date(date) {
+            Some(date) => date,
+            None => {
+                eprintln!("Unable to parse date: format YYYY-MM-DD");
+                return false;
+            }
+        },
+        None => today,
+    };
+    let tax_rate = if matches.is_present("tax") {
+        Some(value_t!(matches, "tax", Decimal).unwrap_or_else(|e| e.exit()))
+    } else {
+        None
+    };
+    let grouping = value_t!(matches, "by", Grouping).unwrap_or(Grouping::Day);
+    let format = value_t!(matches, "format", InvoiceFormat).unwrap_or(InvoiceFormat::Text);
+    let template = match matches.value_of("template") {
+        Some(file) => match std::fs::read_to_string(file) {
+            Ok(t) => Some(t),
+            Err(e) => {
+                eprintln!("Unable to read template {}: {}", file, e);
+                return false;
+            }
+        },
+        None => None,
+    };
+
+    let project = value_t!(matches, "project", String).ok();
+    let invoice = {
+        let filtered;
+        let source = match project {
+            Some(ref p) => {
+                filtered = store.with_project(p);
+                &filtered
+            }
+            None => &*store,
+        };
+        let range = match source.get_range(from, to) {
+            Some(x) => x,
+            None => {
+                eprintln!("No days available from {} to {}!", from, to);
+                return false;
+            }
+        };
+        match Invoice::with_range(&range, &source.get_rates(), grouping) {
+            Ok(x) => x,
+            Err(e) => {
+                eprintln!("{}", e);
+                return false;
+            }
+        }
+    };
+
+    let preview = matches.is_present("preview");
+    let number = if preview {
+        store.peek_invoice_number()
+    } else {
+        store.next_invoice_number()
+    };
+    let mut invoice = invoice
+        .set_number(number)
+        .set_date(date)
+        .set_project(project);
+    if let Some(tax_rate) = tax_rate {
+        invoice = invoice.set_tax_rate(tax_rate);
+    }
+    print!("{}", invoice.render(format, template.as_deref()));
+    !preview
+}
+
+fn subcmd_init(matches: &ArgMatches, pretty: bool, backups: usize) {
     let mut store = data::Storage::default();
 
     let storage_file =
         value_t!(matches, "storage_file", String).unwrap_or_else(|_| "times.json".to_string());
 
     if let Ok(leg_file) = value_t!(matches, "legacy_file", String) {
-        if !store.import_legacy(&leg_file) {
-            println!("Unable to import data!");
-        }
+        import_legacy(&mut store, &leg_file);
     }
 
-    if !store.save(&storage_file, pretty) {
-        println!("Unable to write file: {}", &storage_file);
+    if let Err(e) = store.save(&storage_file, pretty, backups) {
+        println!("Unable to write file {}: {}", &storage_file, e);
         exit(-1);
     }
 
     println!("New store has been created: {}", storage_file);
 }
 
-fn subcmd_show(store: &data::Storage, matches: &ArgMatches) {
+fn print_report(format: Format, report: &Report) {
+    match format {
+        Format::Json => match json::render(report) {
+            Ok(s) => println!("{}", s),
+            Err(e) => eprintln!("Unable to render json: {}", e),
+        },
+        Format::Csv => print!("{}", csv::render(report)),
+        Format::Text => unreachable!("text is rendered by the printer"),
+    }
+}
+
+fn subcmd_show(
+    store: &data::Storage,
+    matches: &ArgMatches,
+    break_rules: &[data::BreakRule],
+    holidays: &Calendar,
+    zone: &Zone,
+    fiscal_year_start: u32,
+) {
     let show_days = matches.is_present("days");
     let mut worked = matches.is_present("worked");
     let breaks = matches.is_present("breaks");
     let verbose = matches.is_present("verbose");
     let parts = matches.is_present("parts");
-    let today = chrono::Utc::today();
+    let format = if matches.is_present("format") {
+        value_t!(matches, "format", Format).unwrap_or_else(|e| e.exit())
+    } else {
+        Format::Text
+    };
+    let today = zone.today();
 
     if !breaks {
         worked = true;
     }
 
-    if let Some(ref matches) = matches.subcommand_matches("year") {
+    let print_text = |p: printer::Printer| {
+        let p = p
+            .set_rates(store.get_rates())
+            .set_vacation_days(store.get_vacation_days())
+            .set_break_rules(break_rules.to_vec())
+            .set_target(store.get_target().cloned())
+            .set_balance(store.balance(holidays, today))
+            .set_holidays(holidays.clone())
+            .show_days(show_days)
+            .show_worked(worked)
+            .show_breaks(breaks)
+            .show_parts(parts)
+            .show_verbose(verbose);
+        print!("{}", p);
+    };
+
+    if let Some(matches) = matches.subcommand_matches("year") {
         let vals_num: Vec<u16> = if matches.is_present("years") {
             values_t!(matches, "years", u16).unwrap_or_else(|e| e.exit())
         } else {
             let c = today.year() as u16;
             if verbose {
-                println!("Assume current year: {}", c);
+                eprintln!("Assume current year: {}", c);
             }
             vec![c]
         };
@@ -360,22 +1062,20 @@ fn subcmd_show(store: &data::Storage, matches: &ArgMatches) {
             match store.get_year(y) {
                 Some(y) => vals.push(y),
                 None => {
-                    println!("Year {} not available!", y);
+                    eprintln!("Year {} not available!", y);
                 }
             }
         }
 
-        let p = printer::Printer::with_years(vals)
-            .set_fee(store.get_fee())
-            .show_days(show_days)
-            .show_worked(worked)
-            .show_breaks(breaks)
-            .show_parts(parts)
-            .show_verbose(verbose);
-        print!("{}", p);
+        if format != Format::Text {
+            print_report(format, &Report::with_years(&vals, &store.get_rates()));
+            return;
+        }
+
+        print_text(printer::Printer::with_years(vals));
     }
 
-    if let Some(ref matches) = matches.subcommand_matches("month") {
+    if let Some(matches) = matches.subcommand_matches("month") {
         let y = if matches.is_present("year") {
             value_t!(matches, "year", u16).unwrap_or_else(|e| e.exit())
         } else {
@@ -387,7 +1087,7 @@ fn subcmd_show(store: &data::Storage, matches: &ArgMatches) {
         } else {
             let c = today.month() as u8;
             if verbose {
-                println!("Assume current month: {}", c);
+                eprintln!("Assume current month: {}", c);
             }
             vec![c]
         };
@@ -397,64 +1097,71 @@ fn subcmd_show(store: &data::Storage, matches: &ArgMatches) {
             match store.get_month(y, x) {
                 Some(x) => vals.push(x),
                 None => {
-                    println!("Month {} not available for year {}!", x, y);
+                    eprintln!("Month {} not available for year {}!", x, y);
                 }
             }
         }
         if vals.is_empty() {
             return;
         }
-        let p = printer::Printer::with_months(vals)
-            .set_fee(store.get_fee())
-            .show_days(show_days)
-            .show_worked(worked)
-            .show_breaks(breaks)
-            .show_parts(parts)
-            .show_verbose(verbose);
-        print!("{}", p);
+        if format != Format::Text {
+            print_report(format, &Report::with_months(&vals, &store.get_rates()));
+            return;
+        }
+        print_text(printer::Printer::with_months(vals));
         return;
     }
 
-    if let Some(ref matches) = matches.subcommand_matches("week") {
-        let y = if matches.is_present("year") {
+    if let Some(matches) = matches.subcommand_matches("week") {
+        let year = if matches.is_present("year") {
             value_t!(matches, "year", u16).unwrap_or_else(|e| e.exit())
         } else {
-            today.year() as u16
+            today.iso_week().year() as u16
         };
 
-        let vals_num: Vec<u32> = if matches.is_present("weeks") {
-            values_t!(matches, "weeks", u32).unwrap_or_else(|e| e.exit())
+        let vals_num: Vec<(u16, u32)> = if matches.is_present("weeks") {
+            let mut weeks = vec![];
+            for w in values_t!(matches, "weeks", String).unwrap_or_else(|e| e.exit()) {
+                match w.parse::<u32>().ok().map(|w| (year, w)) {
+                    Some(x) => weeks.push(x),
+                    None => match legacy_parser::parse_week(&w) {
+                        Some(x) => weeks.push(x),
+                        None => {
+                            eprintln!("Unable to parse week {}: format WW or YYYY-Www", w);
+                            return;
+                        }
+                    },
+                }
+            }
+            weeks
         } else {
             let c = today.iso_week().week();
             if verbose {
-                println!("Assume current week: {}", c);
+                eprintln!("Assume current week: {}", c);
             }
-            vec![c]
+            vec![(year, c)]
         };
 
         let mut vals: Vec<data::Week> = vec![];
-        for x in vals_num {
+        for (y, x) in vals_num {
             match store.get_week(y, x) {
                 Some(x) => vals.push(x),
                 None => {
-                    println!("Week {} not available for year {}!", x, y);
+                    eprintln!("Week {} not available for year {}!", x, y);
                 }
             }
         }
         if vals.is_empty() {
             return;
         }
-        let p = printer::Printer::with_weeks(vals)
-            .set_fee(store.get_fee())
-            .show_days(show_days)
-            .show_worked(worked)
-            .show_breaks(breaks)
-            .show_parts(parts)
-            .show_verbose(verbose);
-        print!("{}", p);
+        if format != Format::Text {
+            print_report(format, &Report::with_weeks(&vals, &store.get_rates()));
+            return;
+        }
+        print_text(printer::Printer::with_weeks(vals));
     }
 
-    if let Some(ref matches) = matches.subcommand_matches("day") {
+    if let Some(matches) = matches.subcommand_matches("day") {
         let y = if matches.is_present("year") {
             value_t!(matches, "year", u16).unwrap_or_else(|e| e.exit())
         } else {
@@ -472,7 +1179,7 @@ fn subcmd_show(store: &data::Storage, matches: &ArgMatches) {
         } else {
             let c = today.day() as u8;
             if verbose {
-                println!("Assume current day: {}", c);
+                eprintln!("Assume current day: {}", c);
             }
             vec![c]
         };
@@ -482,17 +1189,116 @@ fn subcmd_show(store: &data::Storage, matches: &ArgMatches) {
             match store.get_day(y, m, x) {
                 Some(x) => vals.push(x),
                 None => {
-                    println!("Day {} not available for month {} in year {}!", x, m, y);
+                    eprintln!("Day {} not available for month {} in year {}!", x, m, y);
                 }
             }
         }
 
-        let p = printer::Printer::with_days(vals)
-            .show_worked(worked)
-            .show_breaks(breaks)
-            .show_parts(parts)
-            .show_verbose(verbose);
-        print!("{}", p);
-        return;
+        if format != Format::Text {
+            print_report(format, &Report::with_days(&vals, &store.get_rates()));
+            return;
+        }
+
+        print_text(printer::Printer::with_days(vals));
+    }
+
+    if let Some(matches) = matches.subcommand_matches("quarter") {
+        let y = if matches.is_present("year") {
+            value_t!(matches, "year", u16).unwrap_or_else(|e| e.exit())
+        } else {
+            today.year() as u16
+        };
+
+        let vals_num: Vec<u32> = if matches.is_present("quarters") {
+            values_t!(matches, "quarters", u32).unwrap_or_else(|e| e.exit())
+        } else {
+            let c = (today.month() - 1) / 3 + 1;
+            if verbose {
+                eprintln!("Assume current quarter: {}", c);
+            }
+            vec![c]
+        };
+
+        let mut vals: Vec<data::Quarter> = vec![];
+        for x in vals_num {
+            match store.get_quarter(y, x) {
+                Some(x) => vals.push(x),
+                None => {
+                    eprintln!("Quarter {} not available for year {}!", x, y);
+                }
+            }
+        }
+        if vals.is_empty() {
+            return;
+        }
+        if format != Format::Text {
+            print_report(format, &Report::with_quarters(&vals, &store.get_rates()));
+            return;
+        }
+        print_text(printer::Printer::with_quarters(vals));
+    }
+
+    if let Some(matches) = matches.subcommand_matches("fiscal-year") {
+        let vals_num: Vec<u16> = if matches.is_present("years") {
+            values_t!(matches, "years", u16).unwrap_or_else(|e| e.exit())
+        } else {
+            let mut c = today.year() as u16;
+            if today.month() < fiscal_year_start {
+                c -= 1;
+            }
+            if verbose {
+                eprintln!("Assume current fiscal year: {}", c);
+            }
+            vec![c]
+        };
+
+        let mut vals: Vec<data::FiscalYear> = vec![];
+        for x in vals_num {
+            match store.get_fiscal_year(x, fiscal_year_start) {
+                Some(x) => vals.push(x),
+                None => {
+                    eprintln!("Fiscal year {} not available!", x);
+                }
+            }
+        }
+        if vals.is_empty() {
+            return;
+        }
+        if format != Format::Text {
+            print_report(
+                format,
+                &Report::with_fiscal_years(&vals, &store.get_rates()),
+            );
+            return;
+        }
+        print_text(printer::Printer::with_fiscal_years(vals));
+    }
+
+    if let Some(matches) = matches.subcommand_matches("range") {
+        let from = value_t!(matches, "from", String).unwrap_or_else(|e| e.exit());
+        let to = value_t!(matches, "to", String).unwrap_or_else(|_| from.clone());
+        let (from, to) = match (
+            legacy_parser::parse_period(&from, today),
+            legacy_parser::parse_period(&to, today),
+        ) {
+            (Some((from, _)), Some((_, to))) => (from, to),
+            _ => {
+                eprintln!("Unable to parse range: format YYYY-MM-DD or e.g. last-week");
+                return;
+            }
+        };
+
+        let range = match store.get_range(from, to) {
+            Some(x) => x,
+            None => {
+                eprintln!("No days available from {} to {}!", from, to);
+                return;
+            }
+        };
+        if format != Format::Text {
+            print_report(format, &Report::with_ranges(&[range], &store.get_rates()));
+            return;
+        }
+        print_text(printer::Printer::with_ranges(vec![range]));
     }
 }
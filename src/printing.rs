@@ -1 +1,5 @@
+pub mod csv;
+pub mod invoice;
+pub mod json;
 pub mod printer;
+pub mod report;
@@ -0,0 +1,268 @@
+use crate::printing::report::{CurrencyReport, DayReport, PeriodReport, Report};
+use crate::storage::currency::{self, Currency};
+use crate::storage::data;
+use crate::storage::decimal::Decimal;
+use chrono::NaiveDate;
+
+/// The columns of the csv output, not every column is set for every `level`.
+const HEADER: &str =
+    "level,year,month,week,date,start,stop,factor,project,worked_minutes,breaks_minutes,earned,comment,absence,from,to,quarter,currency,billed_minutes,tax";
+
+/// One row of the csv output, the level is one of `year`, `month`, `week`, `range`,
+/// `quarter`, `fiscal-year`, `factor`, `project`, `currency`, `day` or `part`.
+#[derive(Default, Clone, Copy)]
+struct Row<'a> {
+    level: &'a str,
+    year: Option<i32>,
+    month: Option<u32>,
+    week: Option<u32>,
+    date: Option<NaiveDate>,
+    start: Option<&'a str>,
+    stop: Option<&'a str>,
+    factor: Option<Decimal>,
+    project: Option<&'a str>,
+    worked_minutes: Option<i64>,
+    breaks_minutes: Option<i64>,
+    earned: Option<Decimal>,
+    comment: Option<&'a str>,
+    absence: Option<data::Absence>,
+    from: Option<NaiveDate>,
+    to: Option<NaiveDate>,
+    quarter: Option<u32>,
+    currency: Option<&'a Currency>,
+    billed_minutes: Option<i64>,
+    tax: Option<Decimal>,
+}
+
+fn escape(s: &str) -> String {
+    if s.contains(&[',', '"', '\n', '\r'][..]) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
+
+fn opt<T: ToString>(x: Option<T>) -> String {
+    x.map(|x| x.to_string()).unwrap_or_default()
+}
+
+/// Returns the currency of the amounts if they have been earned in a single currency.
+fn single(currencies: &[CurrencyReport]) -> Option<&Currency> {
+    match currencies {
+        [c] => c.currency.as_ref(),
+        _ => None,
+    }
+}
+
+impl<'a> Row<'a> {
+    fn render(&self, out: &mut String) {
+        // amounts have the decimal places of their currency
+        let places = currency::decimals(self.currency) as usize;
+        let amount = |x: Option<Decimal>| opt(x.map(|x| format!("{:.*}", places, x)));
+        let cols = [
+            self.level.to_string(),
+            opt(self.year),
+            opt(self.month),
+            opt(self.week),
+            opt(self.date),
+            opt(self.start),
+            opt(self.stop),
+            opt(self.factor.map(|x| format!("{:.1}", x))),
+            opt(self.project.map(escape)),
+            opt(self.worked_minutes),
+            opt(self.breaks_minutes),
+            amount(self.earned),
+            opt(self.comment.map(escape)),
+            opt(self.absence),
+            opt(self.from),
+            opt(self.to),
+            opt(self.quarter),
+            opt(self.currency),
+            opt(self.billed_minutes),
+            amount(self.tax),
+        ];
+        out.push_str(&cols.join(","));
+        out.push('\n');
+    }
+}
+
+/// Renders one `currency` row per currency, with the columns of `key` set.
+fn render_currencies(out: &mut String, key: Row, currencies: &[CurrencyReport]) {
+    for c in currencies {
+        Row {
+            level: "currency",
+            earned: Some(c.earned),
+            tax: Some(c.tax),
+            currency: c.currency.as_ref(),
+            ..key
+        }
+        .render(out);
+    }
+}
+
+fn render_day(out: &mut String, day: &DayReport) {
+    Row {
+        level: "day",
+        date: Some(day.date),
+        worked_minutes: Some(day.worked_minutes),
+        breaks_minutes: Some(day.breaks_minutes),
+        earned: day.earned,
+        billed_minutes: Some(day.billed_minutes),
+        tax: day.tax,
+        currency: single(&day.currencies),
+        comment: day.comment.as_deref(),
+        absence: day.absence,
+        ..Default::default()
+    }
+    .render(out);
+    if day.currencies.len() > 1 {
+        let key = Row {
+            date: Some(day.date),
+            ..Default::default()
+        };
+        render_currencies(out, key, &day.currencies);
+    }
+
+    for p in &day.parts {
+        Row {
+            level: "part",
+            date: Some(day.date),
+            start: Some(&p.start),
+            stop: p.stop.as_deref(),
+            factor: Some(p.factor),
+            project: p.project.as_deref(),
+            worked_minutes: p.worked_minutes,
+            earned: p.earned,
+            tax: p.tax,
+            currency: p.currency.as_ref(),
+            ..Default::default()
+        }
+        .render(out);
+    }
+}
+
+fn render_period(out: &mut String, level: &str, period: &PeriodReport) {
+    let key = Row {
+        year: Some(period.year),
+        month: period.month,
+        week: period.week,
+        from: period.from,
+        to: period.to,
+        quarter: period.quarter,
+        ..Default::default()
+    };
+    Row {
+        level,
+        worked_minutes: Some(period.worked_minutes),
+        breaks_minutes: Some(period.breaks_minutes),
+        earned: period.earned,
+        billed_minutes: Some(period.billed_minutes),
+        tax: period.tax,
+        currency: single(&period.currencies),
+        ..key
+    }
+    .render(out);
+
+    for f in &period.factors {
+        Row {
+            level: "factor",
+            factor: Some(f.factor),
+            worked_minutes: Some(f.worked_minutes),
+            ..key
+        }
+        .render(out);
+    }
+    for p in &period.projects {
+        Row {
+            level: "project",
+            project: p.project.as_deref(),
+            worked_minutes: Some(p.worked_minutes),
+            earned: p.earned,
+            currency: single(&p.currencies),
+            ..key
+        }
+        .render(out);
+        if p.currencies.len() > 1 {
+            let key = Row {
+                project: p.project.as_deref(),
+                ..key
+            };
+            render_currencies(out, key, &p.currencies);
+        }
+    }
+    render_currencies(out, key, &period.currencies);
+    for d in &period.days {
+        render_day(out, d);
+    }
+}
+
+/// Renders the report as csv with a header line. Every period is followed by its factor,
+/// project and currency breakdown and its days, every day is followed by its parts. Days and
+/// projects with amounts in more than one currency are followed by their currency breakdown,
+/// their `earned` column is empty then. Amounts are written with the decimal places of the
+/// currency in the `currency` column.
+pub fn render(report: &Report) -> String {
+    let mut out = String::from(HEADER);
+    out.push('\n');
+
+    for p in &report.years {
+        render_period(&mut out, "year", p);
+    }
+    for p in &report.months {
+        render_period(&mut out, "month", p);
+    }
+    for p in &report.weeks {
+        render_period(&mut out, "week", p);
+    }
+    for p in &report.ranges {
+        render_period(&mut out, "range", p);
+    }
+    for p in &report.quarters {
+        render_period(&mut out, "quarter", p);
+    }
+    for p in &report.fiscal_years {
+        render_period(&mut out, "fiscal-year", p);
+    }
+    for d in &report.days {
+        render_day(&mut out, d);
+    }
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::storage::legacy_parser;
+
+    #[test]
+    fn test_render_days() {
+        let l = String::from("2017-05-23   08:00-12:00@acme 13:00-  # a, \"b\"");
+        let day = legacy_parser::parse_line(&l).unwrap();
+        let rates = data::Rates {
+            default: Decimal::new(100, 0),
+            currency: "EUR".parse().ok(),
+            ..Default::default()
+        };
+        let report = Report::with_days(&[&day], &rates);
+
+        let expected = format!(
+            "{}\n{}\n{}\n{}\n",
+            HEADER,
+            "day,,,,2017-05-23,,,,,240,60,400.00,\"a, \"\"b\"\"\",,,,,EUR,240,0.00",
+            "part,,,,2017-05-23,08:00,12:00,1.0,acme,240,,400.00,,,,,,EUR,,0.00",
+            "part,,,,2017-05-23,13:00,,1.0,,,,,,,,,,EUR,,",
+        );
+        assert_eq!(expected, render(&report));
+
+        // yen have no decimal places
+        let rates = data::Rates {
+            default: Decimal::new(1000, 0),
+            currency: "JPY".parse().ok(),
+            ..Default::default()
+        };
+        let report = Report::with_days(&[&day], &rates);
+        let csv = render(&report);
+        assert!(csv.contains("day,,,,2017-05-23,,,,,240,60,4000,"));
+        assert!(csv.contains(",acme,240,,4000,,,,,,JPY,,0\n"));
+    }
+}
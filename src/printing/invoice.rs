@@ -0,0 +1,620 @@
+use crate::storage::currency::{self, Currency};
+use crate::storage::data;
+use crate::storage::decimal::Decimal;
+use crate::Error;
+use chrono::NaiveDate;
+use std::collections::{BTreeMap, BTreeSet};
+use std::str::FromStr;
+
+const MARKDOWN: &str = include_str!("../../templates/invoice.md");
+const HTML: &str = include_str!("../../templates/invoice.html");
+const TEXT: &str = include_str!("../../templates/invoice.txt");
+
+/// The output format of an invoice, values are escaped accordingly.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum InvoiceFormat {
+    Markdown,
+    Html,
+    Text,
+}
+
+impl FromStr for InvoiceFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<InvoiceFormat, String> {
+        match s {
+            "markdown" => Ok(InvoiceFormat::Markdown),
+            "html" => Ok(InvoiceFormat::Html),
+            "text" => Ok(InvoiceFormat::Text),
+            _ => Err(format!("Unknown invoice format: {}", s)),
+        }
+    }
+}
+
+impl InvoiceFormat {
+    /// The built-in template of the format, see `templates/` for placeholders.
+    pub fn template(self) -> &'static str {
+        match self {
+            InvoiceFormat::Markdown => MARKDOWN,
+            InvoiceFormat::Html => HTML,
+            InvoiceFormat::Text => TEXT,
+        }
+    }
+
+    fn escape(self, s: &str) -> String {
+        match self {
+            InvoiceFormat::Markdown => s.replace('|', "\\|"),
+            InvoiceFormat::Html => s
+                .replace('&', "&amp;")
+                .replace('<', "&lt;")
+                .replace('>', "&gt;")
+                .replace('"', "&quot;"),
+            InvoiceFormat::Text => s.to_string(),
+        }
+    }
+}
+
+/// How the parts of an invoice are grouped into line items.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Grouping {
+    /// One item per day, described by the date and the comment of the day.
+    Day,
+    /// One item per project, factor and fee.
+    Factor,
+}
+
+impl FromStr for Grouping {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Grouping, String> {
+        match s {
+            "day" => Ok(Grouping::Day),
+            "factor" => Ok(Grouping::Factor),
+            _ => Err(format!("Unknown grouping: {}", s)),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct LineItem {
+    pub description: String,
+    /// The billed hours.
+    pub hours: Decimal,
+    /// The fee per hour, not set if the parts of the item have different fees.
+    pub rate: Option<Decimal>,
+    /// Rounded to the decimal places of the currency.
+    pub amount: Decimal,
+    /// The tax rate in percent charged on the amount.
+    pub tax_rate: Decimal,
+}
+
+/// The tax charged at one rate on the amounts of all line items with that rate.
+#[derive(Debug, PartialEq, Clone)]
+pub struct TaxItem {
+    /// In percent.
+    pub rate: Decimal,
+    pub net: Decimal,
+    /// Rounded to the decimal places of the currency.
+    pub tax: Decimal,
+}
+
+/// An invoice for the parts of a range of days, all charged in the same currency.
+pub struct Invoice {
+    number: u32,
+    date: NaiveDate,
+    from: NaiveDate,
+    to: NaiveDate,
+    project: Option<String>,
+    currency: Option<Currency>,
+    items: Vec<LineItem>,
+}
+
+impl Invoice {
+    /// Creates the line items for the worked parts of the range, taxed at the rates valid for
+    /// their parts (see `data::Rates::tax_for`). The invoice has number 0 and is dated at the
+    /// end of the range until set otherwise.
+    pub fn with_range(
+        range: &data::Range,
+        rates: &data::Rates,
+        grouping: Grouping,
+    ) -> Result<Invoice, Error> {
+        let currencies = range
+            .days
+            .iter()
+            .flat_map(|d| d.parts.iter().filter(|p| !p.is_open()))
+            .map(|p| rates.currency_for(p))
+            .collect::<BTreeSet<_>>();
+        if currencies.len() > 1 {
+            let codes = currencies
+                .iter()
+                .map(|c| c.map_or("none", Currency::code).to_string())
+                .collect();
+            return Err(Error::MixedCurrencies(codes));
+        }
+        let currency = match currencies.into_iter().next() {
+            Some(c) => c.cloned(),
+            None => return Err(Error::NothingToInvoice(range.from, range.to)),
+        };
+
+        let decimals = currency::decimals(currency.as_ref());
+        let items = match grouping {
+            Grouping::Day => items_by_day(range, rates),
+            Grouping::Factor => items_by_factor(range, rates),
+        }
+        .into_iter()
+        .map(|x| LineItem {
+            amount: x.amount.round(decimals),
+            ..x
+        })
+        .collect();
+
+        Ok(Invoice {
+            number: 0,
+            date: range.to,
+            from: range.from,
+            to: range.to,
+            project: None,
+            currency,
+            items,
+        })
+    }
+
+    pub fn set_number(mut self, number: u32) -> Self {
+        self.number = number;
+        self
+    }
+
+    pub fn set_date(mut self, date: NaiveDate) -> Self {
+        self.date = date;
+        self
+    }
+
+    pub fn set_project(mut self, project: Option<String>) -> Self {
+        self.project = project;
+        self
+    }
+
+    /// Sets the tax rate in percent of all line items, replacing the configured rates.
+    pub fn set_tax_rate(mut self, tax_rate: Decimal) -> Self {
+        for x in &mut self.items {
+            x.tax_rate = tax_rate;
+        }
+        self
+    }
+
+    pub fn items(&self) -> &[LineItem] {
+        &self.items
+    }
+
+    pub fn subtotal(&self) -> Decimal {
+        self.items.iter().map(|x| x.amount).sum()
+    }
+
+    /// Returns the tax per rate, ordered by rate. The tax is calculated on the sum of the
+    /// line items with the rate.
+    pub fn taxes(&self) -> Vec<TaxItem> {
+        let mut net: BTreeMap<Decimal, Decimal> = BTreeMap::new();
+        for x in &self.items {
+            *net.entry(x.tax_rate).or_default() += x.amount;
+        }
+        let decimals = currency::decimals(self.currency.as_ref());
+        net.into_iter()
+            .map(|(rate, net)| TaxItem {
+                rate,
+                net,
+                tax: (net * rate).mul_div(1, 100).round(decimals),
+            })
+            .collect()
+    }
+
+    pub fn tax(&self) -> Decimal {
+        self.taxes().iter().map(|x| x.tax).sum()
+    }
+
+    pub fn total(&self) -> Decimal {
+        self.subtotal() + self.tax()
+    }
+
+    fn fmt_amount(&self, amount: Decimal) -> String {
+        match self.currency {
+            Some(ref c) => c.format(amount),
+            None => format!("{:.2}", amount),
+        }
+    }
+
+    /// Renders the invoice with the given template, the built-in template of the format if
+    /// none is given.
+    pub fn render(&self, format: InvoiceFormat, template: Option<&str>) -> String {
+        let template = template.unwrap_or_else(|| format.template());
+        let taxes = self.taxes();
+        // the rate of the invoice, empty if its items are taxed at different rates
+        let tax_rate = match taxes.as_slice() {
+            [x] => x.rate.to_string(),
+            [] => Decimal::ZERO.to_string(),
+            _ => String::new(),
+        };
+        let vars = vec![
+            ("number", self.number.to_string()),
+            ("date", self.date.to_string()),
+            ("from", self.from.to_string()),
+            ("to", self.to.to_string()),
+            (
+                "project",
+                format.escape(self.project.as_deref().unwrap_or("")),
+            ),
+            (
+                "currency",
+                self.currency
+                    .as_ref()
+                    .map(|c| c.code().to_string())
+                    .unwrap_or_default(),
+            ),
+            ("subtotal", self.fmt_amount(self.subtotal())),
+            ("tax_rate", tax_rate),
+            ("tax", self.fmt_amount(self.tax())),
+            ("total", self.fmt_amount(self.total())),
+        ];
+        let items = self
+            .items
+            .iter()
+            .map(|x| {
+                vec![
+                    ("description", format.escape(&x.description)),
+                    ("hours", format!("{:.2}", x.hours)),
+                    (
+                        "rate",
+                        x.rate.map_or("-".to_string(), |r| self.fmt_amount(r)),
+                    ),
+                    ("amount", self.fmt_amount(x.amount)),
+                    ("tax_rate", x.tax_rate.to_string()),
+                ]
+            })
+            .collect::<Vec<_>>();
+        let taxes = taxes
+            .iter()
+            .map(|x| {
+                vec![
+                    ("tax_rate", x.rate.to_string()),
+                    ("net", self.fmt_amount(x.net)),
+                    ("tax", self.fmt_amount(x.tax)),
+                ]
+            })
+            .collect::<Vec<_>>();
+        fill(template, &vars, &[("items", items), ("taxes", taxes)])
+    }
+}
+
+/// Returns the fee of all given parts, `None` if they differ.
+fn common_rate<'a>(
+    date: NaiveDate,
+    parts: impl Iterator<Item = &'a data::Part>,
+    rates: &data::Rates,
+) -> Option<Decimal> {
+    let fees = parts
+        .map(|p| rates.fee_for(date, p))
+        .collect::<BTreeSet<_>>();
+    if fees.len() == 1 {
+        fees.into_iter().next()
+    } else {
+        None
+    }
+}
+
+/// Returns one item per day, a day with parts taxed at different rates gets one item per rate.
+fn items_by_day(range: &data::Range, rates: &data::Rates) -> Vec<LineItem> {
+    let mut items = vec![];
+    for d in &range.days {
+        let mut taxed: BTreeMap<Decimal, Vec<&data::Part>> = BTreeMap::new();
+        for p in d.parts.iter().filter(|p| !p.is_open()) {
+            taxed.entry(rates.tax_for(d.date, p)).or_default().push(p);
+        }
+        let description = match d.comment {
+            Some(ref c) => format!("{}: {}", d.date, c),
+            None => d.date.to_string(),
+        };
+        for (tax_rate, parts) in taxed {
+            items.push(LineItem {
+                description: description.clone(),
+                hours: parts.iter().filter_map(|p| d.part_billed(p, rates)).sum(),
+                rate: common_rate(d.date, parts.iter().copied(), rates),
+                amount: parts.iter().filter_map(|p| d.part_earned(p, rates)).sum(),
+                tax_rate,
+            });
+        }
+    }
+    items
+}
+
+/// The project, factor, fee and tax rate of the parts of an item grouped by factor.
+type FactorKey<'a> = (Option<&'a str>, Decimal, Decimal, Decimal);
+
+fn items_by_factor(range: &data::Range, rates: &data::Rates) -> Vec<LineItem> {
+    // key -> (hours, amount)
+    let mut groups: BTreeMap<FactorKey, (Decimal, Decimal)> = BTreeMap::new();
+    for d in &range.days {
+        for p in d.parts.iter().filter(|p| !p.is_open()) {
+            let key = (
+                p.project.as_deref(),
+                p.factor.unwrap_or(Decimal::ONE),
+                rates.fee_for(d.date, p),
+                rates.tax_for(d.date, p),
+            );
+            let x = groups.entry(key).or_insert((Decimal::ZERO, Decimal::ZERO));
+            x.0 += d.part_billed(p, rates).unwrap_or_default();
+            x.1 += d.part_earned(p, rates).unwrap_or_default();
+        }
+    }
+
+    groups
+        .into_iter()
+        .map(|((project, factor, fee, tax_rate), (hours, amount))| {
+            let mut keys = vec![];
+            if let Some(p) = project {
+                keys.push(p.to_string());
+            }
+            if factor != Decimal::ONE {
+                keys.push(format!("factor {}", factor));
+            }
+            if keys.is_empty() {
+                keys.push("Hours".to_string());
+            }
+            LineItem {
+                description: keys.join(", "),
+                hours,
+                rate: Some(fee),
+                amount,
+                tax_rate,
+            }
+        })
+        .collect()
+}
+
+/// Replaces the `{{name}}` placeholders by the value of the first variable with that name in a
+/// single pass, hence placeholders within the values (e.g. in a comment) are kept as they are.
+fn replace(s: &str, vars: &[&[(&str, String)]]) -> String {
+    let mut out = String::new();
+    let mut rest = s;
+    while let Some(start) = rest.find("{{") {
+        let end = match rest[start..].find("}}") {
+            Some(i) => start + i + 2,
+            None => break,
+        };
+        let name = &rest[start + 2..end - 2];
+        out.push_str(&rest[..start]);
+        match vars.iter().flat_map(|v| v.iter()).find(|(k, _)| *k == name) {
+            Some((_, v)) => out.push_str(v),
+            None => out.push_str(&rest[start..end]),
+        }
+        rest = &rest[end..];
+    }
+    out.push_str(rest);
+    out
+}
+
+type Vars<'a> = Vec<(&'a str, String)>;
+
+/// Replaces the `{{name}}` placeholders of the template. A section `{{#name}}...{{/name}}` of
+/// a list (e.g. `items`) is repeated for every entry of the list, any other section is only
+/// kept if the value of `name` is not empty.
+fn fill(template: &str, vars: &[(&str, String)], lists: &[(&str, Vec<Vars>)]) -> String {
+    let mut out = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find("{{#") {
+        let name_end = match rest[start..].find("}}") {
+            Some(i) => start + i,
+            None => break,
+        };
+        let name = &rest[start + 3..name_end];
+        let close = format!("{{{{/{}}}}}", name);
+        let body_start = name_end + 2;
+        let end = match rest[body_start..].find(&close) {
+            Some(i) => body_start + i,
+            None => break,
+        };
+
+        out.push_str(&replace(&rest[..start], &[vars]));
+        let body = &rest[body_start..end];
+        if let Some((_, list)) = lists.iter().find(|(k, _)| *k == name) {
+            for entry in list {
+                out.push_str(&replace(body, &[entry, vars]));
+            }
+        } else if vars.iter().any(|(k, v)| *k == name && !v.is_empty()) {
+            out.push_str(&replace(body, &[vars]));
+        }
+        rest = &rest[end + close.len()..];
+    }
+    out.push_str(&replace(rest, &[vars]));
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::storage::legacy_parser;
+
+    fn range(days: &[data::Day]) -> data::Range<'_> {
+        data::Range {
+            from: NaiveDate::from_ymd(2024, 3, 1),
+            to: NaiveDate::from_ymd(2024, 3, 31),
+            days: days.iter().collect(),
+        }
+    }
+
+    #[test]
+    fn test_invoice_items() {
+        let days = [
+            "2024-03-04   08:00-12:00 13:00-14:30-1.5  # setup <server>",
+            "2024-03-05   08:00-08:20@acme 09:00-",
+        ]
+        .iter()
+        .map(|l| legacy_parser::parse_line(l).unwrap())
+        .collect::<Vec<_>>();
+        let rates = data::Rates {
+            default: Decimal::new(80, 0),
+            currency: "EUR".parse().ok(),
+            ..Default::default()
+        };
+
+        let invoice = Invoice::with_range(&range(&days), &rates, Grouping::Day)
+            .unwrap()
+            .set_tax_rate(Decimal::new(19, 0));
+        assert_eq!(
+            vec![
+                LineItem {
+                    description: "2024-03-04: setup <server>".to_string(),
+                    hours: Decimal::new(55, 1),
+                    rate: None,
+                    amount: Decimal::new(500, 0),
+                    tax_rate: Decimal::new(19, 0),
+                },
+                LineItem {
+                    description: "2024-03-05".to_string(),
+                    hours: Decimal::new(3333, 4),
+                    rate: Some(Decimal::new(80, 0)),
+                    amount: Decimal::new(2667, 2),
+                    tax_rate: Decimal::new(19, 0),
+                },
+            ],
+            invoice.items()
+        );
+        assert_eq!(Decimal::new(52667, 2), invoice.subtotal());
+        assert_eq!(Decimal::new(10007, 2), invoice.tax());
+        assert_eq!(Decimal::new(62674, 2), invoice.total());
+
+        let invoice = Invoice::with_range(&range(&days), &rates, Grouping::Factor).unwrap();
+        let items = invoice
+            .items()
+            .iter()
+            .map(|x| (x.description.as_str(), x.amount))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                ("Hours", Decimal::new(320, 0)),
+                ("factor 1.5", Decimal::new(180, 0)),
+                ("acme", Decimal::new(2667, 2)),
+            ],
+            items
+        );
+    }
+
+    #[test]
+    fn test_invoice_taxes() {
+        let days = [
+            "2024-06-28   08:00-10:00 10:00-11:00@acme",
+            "2024-07-01   08:00-10:00",
+        ]
+        .iter()
+        .map(|l| legacy_parser::parse_line(l).unwrap())
+        .collect::<Vec<_>>();
+        let mut store = data::Storage::default();
+        store.set_fee(Decimal::new(100, 0));
+        store.set_tax(None, None, Decimal::new(19, 0));
+        store.set_tax(
+            None,
+            Some(NaiveDate::from_ymd(2024, 7, 1)),
+            Decimal::new(16, 0),
+        );
+        store.set_tax(Some("acme".to_string()), None, Decimal::ZERO);
+
+        let invoice =
+            Invoice::with_range(&range(&days), &store.get_rates(), Grouping::Day).unwrap();
+        let items = invoice
+            .items()
+            .iter()
+            .map(|x| (x.description.as_str(), x.amount, x.tax_rate))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                ("2024-06-28", Decimal::new(100, 0), Decimal::ZERO),
+                ("2024-06-28", Decimal::new(200, 0), Decimal::new(19, 0)),
+                ("2024-07-01", Decimal::new(200, 0), Decimal::new(16, 0)),
+            ],
+            items
+        );
+        assert_eq!(
+            vec![Decimal::ZERO, Decimal::new(32, 0), Decimal::new(38, 0)],
+            invoice.taxes().iter().map(|x| x.tax).collect::<Vec<_>>()
+        );
+        assert_eq!(Decimal::new(570, 0), invoice.total());
+
+        let template = "{{#taxes}}[{{tax_rate}}% {{net}} {{tax}}]{{/taxes}} {{tax_rate}}";
+        assert_eq!(
+            "[0% 100.00 0.00][16% 200.00 32.00][19% 200.00 38.00] ",
+            invoice.render(InvoiceFormat::Text, Some(template))
+        );
+        let invoice = invoice.set_tax_rate(Decimal::new(20, 0));
+        assert_eq!(Decimal::new(100, 0), invoice.tax());
+        assert_eq!(
+            "[20% 500.00 100.00] 20",
+            invoice.render(InvoiceFormat::Text, Some(template))
+        );
+    }
+
+    #[test]
+    fn test_invoice_errors() {
+        let days = ["2024-03-04   08:00-12:00 13:00-14:00@acme"]
+            .iter()
+            .map(|l| legacy_parser::parse_line(l).unwrap())
+            .collect::<Vec<_>>();
+        let mut store = data::Storage::default();
+        store.set_rate(
+            Some("acme".to_string()),
+            None,
+            Decimal::new(90, 0),
+            "USD".parse().ok(),
+        );
+        assert!(matches!(
+            Invoice::with_range(&range(&days), &store.get_rates(), Grouping::Day),
+            Err(Error::MixedCurrencies(_))
+        ));
+        assert!(matches!(
+            Invoice::with_range(&range(&[]), &store.get_rates(), Grouping::Day),
+            Err(Error::NothingToInvoice(_, _))
+        ));
+    }
+
+    #[test]
+    fn test_invoice_render() {
+        let days = ["2024-03-04   08:00-10:00  # a | b & c"]
+            .iter()
+            .map(|l| legacy_parser::parse_line(l).unwrap())
+            .collect::<Vec<_>>();
+        let rates = data::Rates {
+            default: Decimal::new(50, 0),
+            ..Default::default()
+        };
+        let invoice = Invoice::with_range(&range(&days), &rates, Grouping::Day)
+            .unwrap()
+            .set_number(7);
+
+        let template = "#{{number}} {{#project}}for {{project}} {{/project}}\
+                        {{#items}}[{{description}} {{hours}}h {{amount}}]{{/items}} = {{total}}";
+        assert_eq!(
+            "#7 [2024-03-04: a | b & c 2.00h 100.00] = 100.00",
+            invoice.render(InvoiceFormat::Text, Some(template))
+        );
+        let invoice = invoice.set_project(Some("acme".to_string()));
+        assert_eq!(
+            "#7 for acme [2024-03-04: a | b &amp; c 2.00h 100.00] = 100.00",
+            invoice.render(InvoiceFormat::Html, Some(template))
+        );
+
+        let md = invoice.render(InvoiceFormat::Markdown, None);
+        assert!(md.starts_with("# Invoice 7\n"));
+        assert!(md.contains("| 2024-03-04: a \\| b & c | 2.00 | 50.00 | 100.00 |\n"));
+        assert!(md.contains("Project: acme\n"));
+
+        // placeholders in comments are not replaced
+        let days = ["2024-03-04   08:00-10:00  # see {{total}} of {{number}}"]
+            .iter()
+            .map(|l| legacy_parser::parse_line(l).unwrap())
+            .collect::<Vec<_>>();
+        let invoice = Invoice::with_range(&range(&days), &rates, Grouping::Day)
+            .unwrap()
+            .set_number(7);
+        assert_eq!(
+            "#7 [2024-03-04: see {{total}} of {{number}} 2.00h 100.00] = 100.00",
+            invoice.render(InvoiceFormat::Text, Some(template))
+        );
+    }
+}
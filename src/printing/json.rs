@@ -0,0 +1,6 @@
+use crate::printing::report::Report;
+
+/// Renders the report as a (pretty printed) json document.
+pub fn render(report: &Report) -> serde_json::Result<String> {
+    serde_json::to_string_pretty(report)
+}
@@ -1,6 +1,9 @@
-use crate::storage::data;
+use crate::storage::currency::{Currency, Earnings};
+use crate::storage::data::{self, Period};
+use crate::storage::decimal::Decimal;
+use crate::storage::holidays::Calendar;
 use chrono::Duration;
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 use std::fmt;
 
 pub struct Printer<'a> {
@@ -8,78 +11,125 @@ pub struct Printer<'a> {
     months: Vec<data::Month<'a>>,
     weeks: Vec<data::Week<'a>>,
     days: Vec<&'a data::Day>,
+    ranges: Vec<data::Range<'a>>,
+    quarters: Vec<data::Quarter<'a>>,
+    fiscal_years: Vec<data::FiscalYear<'a>>,
 
     worked: bool,
     breaks: bool,
     verbose: bool,
     show_days: bool,
     parts: bool,
-    fee: f32,
+    rates: data::Rates,
+    break_rules: Vec<data::BreakRule>,
+    target: Option<data::Target>,
+    balance: Option<data::Balance>,
+    vacation_days: Option<u32>,
+    holidays: Calendar,
 }
 
 impl<'a> Printer<'a> {
-    pub fn with_years(years: Vec<&'a data::Year>) -> Printer<'a> {
+    fn new() -> Printer<'a> {
         Printer {
-            years,
+            years: vec![],
             months: vec![],
             weeks: vec![],
             days: vec![],
+            ranges: vec![],
+            quarters: vec![],
+            fiscal_years: vec![],
             worked: false,
             breaks: false,
             verbose: false,
             show_days: false,
             parts: false,
-            fee: 0.0,
+            rates: data::Rates::default(),
+            break_rules: vec![],
+            target: None,
+            balance: None,
+            vacation_days: None,
+            holidays: Calendar::default(),
+        }
+    }
+
+    pub fn with_years(years: Vec<&'a data::Year>) -> Printer<'a> {
+        Printer {
+            years,
+            ..Printer::new()
         }
     }
 
     pub fn with_months(months: Vec<data::Month<'a>>) -> Printer<'a> {
         Printer {
-            years: vec![],
             months,
-            weeks: vec![],
-            days: vec![],
-            worked: false,
-            breaks: false,
-            verbose: false,
-            show_days: false,
-            parts: false,
-            fee: 0.0,
+            ..Printer::new()
         }
     }
 
     pub fn with_weeks(weeks: Vec<data::Week<'a>>) -> Printer<'a> {
         Printer {
-            years: vec![],
-            months: vec![],
             weeks,
-            days: vec![],
-            worked: false,
-            breaks: false,
-            verbose: false,
-            show_days: false,
-            parts: false,
-            fee: 0.0,
+            ..Printer::new()
         }
     }
 
     pub fn with_days(days: Vec<&'a data::Day>) -> Printer<'a> {
         Printer {
-            years: vec![],
-            months: vec![],
-            weeks: vec![],
             days,
-            worked: false,
-            breaks: false,
-            verbose: false,
-            show_days: false,
-            parts: false,
-            fee: 0.0,
+            ..Printer::new()
+        }
+    }
+
+    pub fn with_ranges(ranges: Vec<data::Range<'a>>) -> Printer<'a> {
+        Printer {
+            ranges,
+            ..Printer::new()
+        }
+    }
+
+    pub fn with_quarters(quarters: Vec<data::Quarter<'a>>) -> Printer<'a> {
+        Printer {
+            quarters,
+            ..Printer::new()
+        }
+    }
+
+    pub fn with_fiscal_years(fiscal_years: Vec<data::FiscalYear<'a>>) -> Printer<'a> {
+        Printer {
+            fiscal_years,
+            ..Printer::new()
         }
     }
 
-    pub fn set_fee(mut self, val: f32) -> Self {
-        self.fee = val;
+    pub fn set_rates(mut self, val: data::Rates) -> Self {
+        self.rates = val;
+        self
+    }
+
+    pub fn set_break_rules(mut self, val: Vec<data::BreakRule>) -> Self {
+        self.break_rules = val;
+        self
+    }
+
+    pub fn set_target(mut self, val: Option<data::Target>) -> Self {
+        self.target = val;
+        self
+    }
+
+    /// Sets the cumulative balance, the balance of each period is calculated until its date.
+    pub fn set_balance(mut self, val: Option<data::Balance>) -> Self {
+        self.balance = val;
+        self
+    }
+
+    /// Sets the holidays which are marked and not expected to be worked.
+    pub fn set_holidays(mut self, val: Calendar) -> Self {
+        self.holidays = val;
+        self
+    }
+
+    pub fn set_vacation_days(mut self, val: Option<u32>) -> Self {
+        self.vacation_days = val;
         self
     }
 
@@ -129,24 +179,37 @@ impl<'a> Printer<'a> {
         if self.verbose {
             write!(f, " ({})", day.date.format("%a"))?;
         }
+        if let Some(a) = day.absence {
+            write!(f, " [{}]", a)?;
+        }
+        if let Some(h) = self.holidays.holiday(day.date) {
+            write!(f, " [{}]", h.name)?;
+        }
         if self.worked {
             let w = day.worked().num_minutes() as f64 / 60.0;
-            write!(f, " worked: {: >5.2}h", w)?;
+            write!(f, " worked: {: >5.2}h{}", w, self.fmt_billed(&[day]))?;
         }
         if self.breaks {
-            //let b = 0;
-            write!(f, " TODO!!! breaks: FOOBARh")?;
+            let b = day.breaks().num_minutes() as f64 / 60.0;
+            write!(f, " breaks: {: >5.2}h", b)?;
+            if let Some(r) = day.missing_break(&self.break_rules) {
+                write!(f, " (break of {}min required!)", r.min)?;
+            }
         }
         if self.parts {
             let s = &day
                 .parts
                 .iter()
-                .map(|ref x| {
+                .map(|x| {
                     let s = format!(
-                        "{}-{} f: {:.1}",
+                        "{}-{} f: {:.1}{}",
                         x.start.format("%H:%M"),
-                        x.stop.unwrap().format("%H:%M"),
-                        x.factor.unwrap_or(1.0)
+                        x.fmt_stop(),
+                        x.factor.unwrap_or(Decimal::ONE),
+                        match x.project {
+                            Some(ref p) => format!(" @{}", p),
+                            None => "".to_string(),
+                        }
                     );
                     s
                 })
@@ -156,7 +219,7 @@ impl<'a> Printer<'a> {
         }
         if self.verbose {
             write!(f, " ({} parts)", day.parts.len())?;
-            write!(f, " earned: {:.2}", day.earned(self.fee))?;
+            write!(f, " earned: {}", day.earned(&self.rates))?;
         }
         if let Some(ref c) = day.comment {
             writeln!(f, "  ({})", &c)
@@ -165,44 +228,127 @@ impl<'a> Printer<'a> {
         }
     }
 
-    fn fmt_days(&self, f: &mut fmt::Formatter, days: &[&'a data::Day]) -> fmt::Result {
-        for d in days {
-            self.fmt_day(f, d)?
+    /// Formats the billed time of the given days to be shown next to the worked time, e.g.
+    /// " (billed: 6.25h)", empty if the exact time is billed.
+    fn fmt_billed(&self, days: &[&data::Day]) -> String {
+        if self.rates.billing.is_none() {
+            return String::new();
         }
-        Ok(())
+        let billed = days
+            .iter()
+            .fold(Duration::zero(), |a, d| a + d.billed(&self.rates));
+        format!(" (billed: {:.2}h)", billed.num_minutes() as f64 / 60.0)
     }
 
-    fn fmt_week(&self, f: &mut fmt::Formatter, week: &'a data::Week) -> fmt::Result {
-        writeln!(f, "Week: {}", week.as_num())?;
-
-        if self.verbose {
-            writeln!(f, "Days recorded: {}", week.days.len())?;
+    /// Prints worked time and earnings grouped by project, nothing if no part has a project.
+    fn fmt_projects(&self, f: &mut fmt::Formatter, days: &[&data::Day]) -> fmt::Result {
+        let mut projects: BTreeMap<Option<&str>, (Duration, Earnings)> = BTreeMap::new();
+        for d in days {
+            for p in &d.parts {
+                if let (Some(worked), Some(earned)) = (p.worked(), d.part_earned(p, &self.rates)) {
+                    let x = projects
+                        .entry(p.project.as_deref())
+                        .or_insert((Duration::zero(), Earnings::default()));
+                    x.0 = x.0 + worked;
+                    x.1.add(self.rates.currency_for(p), earned);
+                }
+            }
         }
 
-        if self.show_days {
-            self.fmt_days(f, &week.days)?;
+        if projects.keys().all(Option::is_none) {
+            return Ok(());
         }
 
-        if self.worked {
-            let w = week.worked().num_minutes() as f64 / 60.0;
-            writeln!(f, "total worked: {:.2}h", w)?;
+        for (k, (worked, earned)) in &projects {
+            let w = worked.num_minutes() as f64 / 60.0;
             writeln!(
                 f,
-                "avg worked per day: {:.2}h/day",
-                w / week.days.len() as f64
-            )?;
+                "Worked project {}: {:.2}h - earned: {}",
+                k.unwrap_or("-"),
+                w,
+                earned
+            )?
         }
-        if self.breaks {
-            writeln!(f, "The breaks shall go here!")?;
+        Ok(())
+    }
+
+    /// Prints the total and average breaks of the days and a warning for every day which
+    /// violates a minimum break rule.
+    fn fmt_breaks(&self, f: &mut fmt::Formatter, days: &[&data::Day]) -> fmt::Result {
+        let mut breaks = Duration::zero();
+        for d in days {
+            breaks = breaks + d.breaks();
         }
+        let b = breaks.num_minutes() as f64 / 60.0;
+        writeln!(f, "total breaks: {:.2}h", b)?;
+        writeln!(f, "avg breaks per day: {:.2}h/day", b / worked_days(days))?;
 
-        if self.verbose {
-            writeln!(f, "total earned: {:.2}", week.earned(self.fee))?;
+        for d in days {
+            if let Some(r) = d.missing_break(&self.break_rules) {
+                writeln!(
+                    f,
+                    "Warning: {} has {}min of breaks, {}min are required after {:.2}h worked",
+                    d.date,
+                    d.breaks().num_minutes(),
+                    r.min,
+                    r.after as f64 / 60.0
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Prints the tax on the (net) earned amount of the days and the gross amount, nothing if
+    /// no tax rate has been set.
+    fn fmt_tax(
+        &self,
+        f: &mut fmt::Formatter,
+        label: &str,
+        days: &[&data::Day],
+        earned: Earnings,
+    ) -> fmt::Result {
+        if self.rates.taxes.is_empty() {
+            return Ok(());
         }
+        let tax = days.tax(&self.rates);
+        let mut gross = earned;
+        gross += tax.clone();
+        writeln!(f, "{} tax: {}", label, tax)?;
+        writeln!(f, "{} gross: {}", label, gross)
+    }
+
+    /// Returns the target and the date periods are balanced until, if a target has been set.
+    fn target(&self) -> Option<(&data::Target, chrono::NaiveDate)> {
+        match (&self.target, &self.balance) {
+            (Some(t), Some(b)) => Some((t, b.until)),
+            _ => None,
+        }
+    }
 
+    fn fmt_balance(&self, f: &mut fmt::Formatter, balance: &data::Balance) -> fmt::Result {
+        writeln!(
+            f,
+            "expected until {}: {:.2}h - overtime: {:+.2}h",
+            balance.until,
+            balance.expected.num_minutes() as f64 / 60.0,
+            balance.overtime().num_minutes() as f64 / 60.0
+        )
+    }
+
+    fn fmt_days(&self, f: &mut fmt::Formatter, days: &[&'a data::Day]) -> fmt::Result {
+        for d in days {
+            self.fmt_day(f, d)?
+        }
         Ok(())
     }
 
+    fn fmt_week(&self, f: &mut fmt::Formatter, week: &'a data::Week) -> fmt::Result {
+        writeln!(f, "Week: {} - {}", week.as_num(), week.as_iso())?;
+        self.fmt_period(f, &week.days, |t, until| {
+            week.balance(t, &self.holidays, until)
+        })
+    }
+
     fn fmt_weeks(&self, f: &mut fmt::Formatter, weeks: &[data::Week]) -> fmt::Result {
         for week in weeks {
             self.fmt_week(f, week)?;
@@ -210,56 +356,98 @@ impl<'a> Printer<'a> {
         Ok(())
     }
 
-    fn fmt_month(&self, f: &mut fmt::Formatter, month: &'a data::Month) -> fmt::Result {
-        writeln!(f, "Month: {} - {}", month.as_num(), month.as_name())?;
+    fn fmt_range(&self, f: &mut fmt::Formatter, range: &'a data::Range) -> fmt::Result {
+        writeln!(f, "Range: {} - {}", range.from, range.to)?;
+        self.fmt_period(f, &range.days, |t, until| {
+            range.balance(t, &self.holidays, until)
+        })
+    }
 
+    /// Prints the days, totals, factor and project breakdown of a week, month, quarter, fiscal
+    /// year or range.
+    /// The balance of the period is calculated by `balance` from the target and the date until
+    /// which periods are balanced.
+    fn fmt_period<B>(
+        &self,
+        f: &mut fmt::Formatter,
+        days: &[&'a data::Day],
+        balance: B,
+    ) -> fmt::Result
+    where
+        B: Fn(&data::Target, chrono::NaiveDate) -> data::Balance,
+    {
         if self.verbose {
-            writeln!(f, "Days recorded: {}", month.days.len())?;
+            writeln!(f, "Days recorded: {}", days.len())?;
+            fmt_absences(f, days)?;
         }
 
         if self.show_days {
-            self.fmt_days(f, &month.days)?;
+            self.fmt_days(f, days)?;
         }
 
         if self.worked {
-            let w = month.worked().num_minutes() as f64 / 60.0;
-            writeln!(f, "total worked: {:.2}h", w)?;
-            writeln!(
-                f,
-                "avg worked per day: {:.2}h/day",
-                w / month.days.len() as f64
-            )?;
+            let w = days.worked().num_minutes() as f64 / 60.0;
+            writeln!(f, "total worked: {:.2}h{}", w, self.fmt_billed(days))?;
+            writeln!(f, "avg worked per day: {:.2}h/day", w / worked_days(days))?;
+            if let Some((t, until)) = self.target() {
+                self.fmt_balance(f, &balance(t, until))?;
+            }
         }
         if self.breaks {
-            writeln!(f, "The breaks shall go here!")?
+            self.fmt_breaks(f, days)?;
         }
 
         if self.verbose {
-            let mut times_fac: HashMap<usize, Duration> = HashMap::new();
-            for d in &month.days {
-                for ref p in &d.parts {
-                    let f = (p.factor.unwrap_or(1.0) * 10.0) as usize;
+            let mut times_fac: BTreeMap<Decimal, Duration> = BTreeMap::new();
+            for d in days {
+                for p in &d.parts {
+                    let f = p.factor.unwrap_or(Decimal::ONE).round(1);
                     if let Some(worked) = p.worked() {
-                        if let Some(x) = times_fac.get_mut(&f) {
-                            *x = *x + worked;
-                        } else {
-                            times_fac.insert(f, worked);
-                        }
+                        let x = times_fac.entry(f).or_insert_with(Duration::zero);
+                        *x = *x + worked;
                     }
                 }
             }
 
             for (k, v) in &times_fac {
                 let w = v.num_minutes() as f64 / 60.0;
-                writeln!(f, "Worked factor {:.1}: {:.2}h", (*k as f32 / 10.0), w)?
+                writeln!(f, "Worked factor {:.1}: {:.2}h", k, w)?
             }
+            self.fmt_projects(f, days)?;
 
-            writeln!(f, "total earned: {:.2}", month.earned(self.fee))?
+            let earned = days.earned(&self.rates);
+            writeln!(f, "total earned: {}", earned)?;
+            self.fmt_tax(f, "total", days, earned)?;
         }
 
         Ok(())
     }
 
+    fn fmt_month(&self, f: &mut fmt::Formatter, month: &'a data::Month) -> fmt::Result {
+        writeln!(f, "Month: {} - {}", month.as_num(), month.as_name())?;
+        self.fmt_period(f, &month.days, |t, until| {
+            month.balance(t, &self.holidays, until)
+        })
+    }
+
+    fn fmt_quarter(&self, f: &mut fmt::Formatter, quarter: &'a data::Quarter) -> fmt::Result {
+        writeln!(f, "Quarter: Q{} {}", quarter.quarter, quarter.year)?;
+        self.fmt_period(f, &quarter.days, |t, until| {
+            quarter.balance(t, &self.holidays, until)
+        })
+    }
+
+    fn fmt_fiscal_year(&self, f: &mut fmt::Formatter, fy: &'a data::FiscalYear) -> fmt::Result {
+        writeln!(
+            f,
+            "Fiscal year: {} ({} - {})",
+            fy.year,
+            fy.first(),
+            fy.last()
+        )?;
+        self.fmt_period(f, &fy.days, |t, until| fy.balance(t, &self.holidays, until))
+    }
+
     fn fmt_months(&self, f: &mut fmt::Formatter, months: &[data::Month]) -> fmt::Result {
         for month in months {
             self.fmt_month(f, month)?;
@@ -272,20 +460,47 @@ impl<'a> Printer<'a> {
         writeln!(f, "Year: {}, {} month(s) recorded", year.year, months.len())?;
 
         let mut worked: f64 = 0.0;
-        let mut earned: f64 = 0.0;
+        // amounts in different currencies are kept apart
+        let mut earned = Earnings::default();
+        let mut breaks: f64 = 0.0;
         for m in months {
             worked += m.worked().num_minutes() as f64 / 60.0;
-            earned += m.earned(self.fee) as f64;
+            breaks += m.breaks().num_minutes() as f64 / 60.0;
+            earned += m.earned(&self.rates);
             self.fmt_month(f, &m)?;
             writeln!(f, "-------")?;
         }
         if self.worked {
+            let days = year.days.iter().collect::<Vec<_>>();
+            writeln!(
+                f,
+                "Accumulated worked: {:.2}h{} - earned: {}",
+                worked,
+                self.fmt_billed(&days),
+                earned
+            )?;
+            if let Some((t, until)) = self.target() {
+                self.fmt_balance(f, &year.balance(t, &self.holidays, until))?;
+            }
+        }
+        if self.breaks {
+            writeln!(f, "Accumulated breaks: {:.2}h", breaks)?;
+        }
+        if let Some(allowance) = self.vacation_days {
+            let taken = year.absences(data::Absence::Vacation);
             writeln!(
                 f,
-                "Accumulated worked: {:.2}h - earned: {:.2}",
-                worked, earned
+                "Vacation: {} of {} day(s) taken, {} remaining",
+                taken,
+                allowance,
+                i64::from(allowance) - taken as i64
             )?;
         }
+        if self.verbose {
+            let days = year.days.iter().collect::<Vec<_>>();
+            self.fmt_projects(f, &days)?;
+            self.fmt_tax(f, "Accumulated", &days, earned)?;
+        }
         Ok(())
     }
 
@@ -297,9 +512,74 @@ impl<'a> Printer<'a> {
     }
 }
 
+/// Returns the number of days with worked parts, at least 1 to be used as divisor.
+fn worked_days(days: &[&data::Day]) -> f64 {
+    days.iter().filter(|d| !d.parts.is_empty()).count().max(1) as f64
+}
+
+/// Prints the number of days per absence, nothing if there are none.
+fn fmt_absences(f: &mut fmt::Formatter, days: &[&data::Day]) -> fmt::Result {
+    let mut absences: BTreeMap<data::Absence, usize> = BTreeMap::new();
+    for d in days {
+        if let Some(a) = d.absence {
+            *absences.entry(a).or_insert(0) += 1;
+        }
+    }
+    if absences.is_empty() {
+        return Ok(());
+    }
+    let s = absences
+        .iter()
+        .map(|(k, v)| format!("{}: {}", k, v))
+        .collect::<Vec<_>>()
+        .join(", ");
+    writeln!(f, "Days absent: {}", s)
+}
+
+/// Formats the project and/or factor a rate is bound to, e.g. "(acme, factor 1.5)".
+pub fn fmt_rate_key(rate: &data::Rate) -> String {
+    let mut keys = vec![];
+    if let Some(ref p) = rate.project {
+        keys.push(p.clone());
+    }
+    if let Some(factor) = rate.factor {
+        keys.push(format!("factor {:.1}", factor));
+    }
+    format!("({})", keys.join(", "))
+}
+
+/// Formats a fee in the given currency, with two decimal places if no currency has been set.
+pub fn fmt_fee(fee: Decimal, currency: Option<&Currency>) -> String {
+    match currency {
+        Some(c) => c.format(fee),
+        None => format!("{:.2}", fee),
+    }
+}
+
 impl<'a> fmt::Display for Printer<'a> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        writeln!(f, "Assumed fee per hour: {:.2}", self.fee)?;
+        let currency = self.rates.currency.as_ref();
+        writeln!(
+            f,
+            "Assumed fee per hour: {}",
+            fmt_fee(self.rates.default, currency)
+        )?;
+        for x in &self.rates.history {
+            writeln!(
+                f,
+                "Assumed fee per hour from {}: {}",
+                x.effective_from,
+                fmt_fee(x.fee, currency)
+            )?;
+        }
+        for r in &self.rates.overrides {
+            writeln!(
+                f,
+                "Assumed fee per hour {}: {}",
+                fmt_rate_key(r),
+                fmt_fee(r.fee, r.currency.as_ref().or(currency))
+            )?;
+        }
         if !self.years.is_empty() {
             self.fmt_years(f, &self.years)?;
         }
@@ -312,6 +592,23 @@ impl<'a> fmt::Display for Printer<'a> {
         if !self.days.is_empty() {
             self.fmt_days(f, &self.days)?;
         }
+        for range in &self.ranges {
+            self.fmt_range(f, range)?;
+        }
+        for quarter in &self.quarters {
+            self.fmt_quarter(f, quarter)?;
+        }
+        for fy in &self.fiscal_years {
+            self.fmt_fiscal_year(f, fy)?;
+        }
+        if let Some(ref b) = self.balance {
+            writeln!(
+                f,
+                "Overtime balance as of {}: {:+.2}h",
+                b.until,
+                b.overtime().num_minutes() as f64 / 60.0
+            )?;
+        }
         Ok(())
     }
 }
@@ -0,0 +1,451 @@
+use crate::storage::currency::{Currency, Earnings};
+use crate::storage::data::{self, Period};
+use crate::storage::decimal::Decimal;
+use chrono::{Datelike, NaiveDate};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+/// The output format of `show`, `Text` is rendered by the `Printer`.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Format {
+    Text,
+    Json,
+    Csv,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Format, String> {
+        match s {
+            "text" => Ok(Format::Text),
+            "json" => Ok(Format::Json),
+            "csv" => Ok(Format::Csv),
+            _ => Err(format!("Unknown format: {}", s)),
+        }
+    }
+}
+
+/// The machine-readable aggregation of the shown years, months, weeks or days, rendered by the
+/// `json` and `csv` modules.
+#[derive(Serialize, Debug, PartialEq)]
+pub struct Report {
+    pub fee_per_hour: Decimal,
+    pub currency: Option<Currency>,
+    pub years: Vec<PeriodReport>,
+    pub months: Vec<PeriodReport>,
+    pub weeks: Vec<PeriodReport>,
+    pub days: Vec<DayReport>,
+    pub ranges: Vec<PeriodReport>,
+    pub quarters: Vec<PeriodReport>,
+    pub fiscal_years: Vec<PeriodReport>,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct PeriodReport {
+    pub year: i32,
+    /// Set for months, 1 based.
+    pub month: Option<u32>,
+    /// Set for weeks, the ISO week number, `year` is the ISO year then.
+    pub week: Option<u32>,
+    /// Set for quarters, 1 based.
+    pub quarter: Option<u32>,
+    /// Set for ranges and fiscal years, the first day.
+    pub from: Option<NaiveDate>,
+    /// Set for ranges and fiscal years, the last day.
+    pub to: Option<NaiveDate>,
+    pub days_recorded: usize,
+    pub worked_minutes: i64,
+    pub breaks_minutes: i64,
+    /// The worked time rounded for billing, see `data::Billing`.
+    pub billed_minutes: i64,
+    /// Not set if amounts in more than one currency have been earned, see `currencies`.
+    pub earned: Option<Decimal>,
+    /// The tax on `earned`, see `data::Tax`.
+    pub tax: Option<Decimal>,
+    /// `earned` plus `tax`.
+    pub gross: Option<Decimal>,
+    pub factors: Vec<FactorReport>,
+    pub projects: Vec<ProjectReport>,
+    pub currencies: Vec<CurrencyReport>,
+    pub days: Vec<DayReport>,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct FactorReport {
+    pub factor: Decimal,
+    pub worked_minutes: i64,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct ProjectReport {
+    pub project: Option<String>,
+    pub worked_minutes: i64,
+    /// Not set if amounts in more than one currency have been earned, see `currencies`.
+    pub earned: Option<Decimal>,
+    pub currencies: Vec<CurrencyReport>,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct CurrencyReport {
+    /// Not set if no currency has been configured.
+    pub currency: Option<Currency>,
+    pub earned: Decimal,
+    pub tax: Decimal,
+    pub gross: Decimal,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct DayReport {
+    pub date: NaiveDate,
+    pub worked_minutes: i64,
+    pub breaks_minutes: i64,
+    pub billed_minutes: i64,
+    /// Not set if amounts in more than one currency have been earned, see `currencies`.
+    pub earned: Option<Decimal>,
+    pub tax: Option<Decimal>,
+    pub currencies: Vec<CurrencyReport>,
+    pub comment: Option<String>,
+    pub absence: Option<data::Absence>,
+    pub parts: Vec<PartReport>,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct PartReport {
+    /// Formatted as `HH:MM`.
+    pub start: String,
+    /// Formatted as `HH:MM`, followed by `+1` if on the next day, not set for a running part.
+    pub stop: Option<String>,
+    pub factor: Decimal,
+    pub project: Option<String>,
+    /// Not set for a running part.
+    pub worked_minutes: Option<i64>,
+    /// Not set for a running part.
+    pub earned: Option<Decimal>,
+    /// Not set for a running part.
+    pub tax: Option<Decimal>,
+    pub currency: Option<Currency>,
+}
+
+impl Report {
+    fn new(rates: &data::Rates) -> Report {
+        Report {
+            fee_per_hour: rates.default,
+            currency: rates.currency.clone(),
+            years: vec![],
+            months: vec![],
+            weeks: vec![],
+            days: vec![],
+            ranges: vec![],
+            quarters: vec![],
+            fiscal_years: vec![],
+        }
+    }
+
+    pub fn with_years(years: &[&data::Year], rates: &data::Rates) -> Report {
+        let mut r = Report::new(rates);
+        for y in years {
+            let days = y.days.iter().collect::<Vec<_>>();
+            let mut p = PeriodReport::new(&days, rates);
+            p.year = i32::from(y.year);
+            r.years.push(p);
+        }
+        r
+    }
+
+    pub fn with_months(months: &[data::Month], rates: &data::Rates) -> Report {
+        let mut r = Report::new(rates);
+        for m in months {
+            let mut p = PeriodReport::new(&m.days, rates);
+            p.month = m.days.first().map(|d| d.date.month());
+            r.months.push(p);
+        }
+        r
+    }
+
+    pub fn with_weeks(weeks: &[data::Week], rates: &data::Rates) -> Report {
+        let mut r = Report::new(rates);
+        for w in weeks {
+            let mut p = PeriodReport::new(&w.days, rates);
+            if let Some(d) = w.days.first() {
+                p.year = d.date.iso_week().year();
+                p.week = Some(d.date.iso_week().week());
+            }
+            r.weeks.push(p);
+        }
+        r
+    }
+
+    pub fn with_ranges(ranges: &[data::Range], rates: &data::Rates) -> Report {
+        let mut r = Report::new(rates);
+        for range in ranges {
+            let mut p = PeriodReport::new(&range.days, rates);
+            p.year = range.from.year();
+            p.from = Some(range.from);
+            p.to = Some(range.to);
+            r.ranges.push(p);
+        }
+        r
+    }
+
+    pub fn with_quarters(quarters: &[data::Quarter], rates: &data::Rates) -> Report {
+        let mut r = Report::new(rates);
+        for q in quarters {
+            let mut p = PeriodReport::new(&q.days, rates);
+            p.year = q.year;
+            p.quarter = Some(q.quarter);
+            r.quarters.push(p);
+        }
+        r
+    }
+
+    /// The year of a fiscal year is the calendar year it starts in.
+    pub fn with_fiscal_years(fiscal_years: &[data::FiscalYear], rates: &data::Rates) -> Report {
+        let mut r = Report::new(rates);
+        for fy in fiscal_years {
+            let mut p = PeriodReport::new(&fy.days, rates);
+            p.year = fy.year;
+            p.from = Some(fy.first());
+            p.to = Some(fy.last());
+            r.fiscal_years.push(p);
+        }
+        r
+    }
+
+    pub fn with_days(days: &[&data::Day], rates: &data::Rates) -> Report {
+        let mut r = Report::new(rates);
+        r.days = days.iter().map(|d| DayReport::new(d, rates)).collect();
+        r
+    }
+}
+
+impl CurrencyReport {
+    /// Returns the earned amount, the tax and the gross amount per currency.
+    fn with_earnings(earned: &Earnings, tax: &Earnings) -> Vec<CurrencyReport> {
+        earned
+            .iter()
+            .map(|(c, earned)| CurrencyReport {
+                currency: c.cloned(),
+                earned,
+                tax: tax.get(c),
+                gross: earned + tax.get(c),
+            })
+            .collect()
+    }
+}
+
+impl PeriodReport {
+    fn new(days: &[&data::Day], rates: &data::Rates) -> PeriodReport {
+        // factors are grouped by one decimal, as in the text output
+        let mut factors: BTreeMap<Decimal, i64> = BTreeMap::new();
+        let mut projects: BTreeMap<Option<&str>, (i64, Earnings, Earnings)> = BTreeMap::new();
+        for d in days {
+            for p in &d.parts {
+                if let (Some(worked), Some(earned)) = (p.worked(), d.part_earned(p, rates)) {
+                    let f = p.factor.unwrap_or(Decimal::ONE).round(1);
+                    *factors.entry(f).or_insert(0) += worked.num_minutes();
+                    let x = projects.entry(p.project.as_deref()).or_default();
+                    x.0 += worked.num_minutes();
+                    x.1.add(rates.currency_for(p), earned);
+                    x.2.add(
+                        rates.currency_for(p),
+                        d.part_tax(p, rates).unwrap_or_default(),
+                    );
+                }
+            }
+        }
+
+        let earned = days.earned(rates);
+        let tax = days.tax(rates);
+        let mut gross = earned.clone();
+        gross += tax.clone();
+
+        let days = days
+            .iter()
+            .map(|d| DayReport::new(d, rates))
+            .collect::<Vec<_>>();
+        PeriodReport {
+            year: days.first().map(|d| d.date.year()).unwrap_or_default(),
+            month: None,
+            week: None,
+            quarter: None,
+            from: None,
+            to: None,
+            days_recorded: days.len(),
+            worked_minutes: days.iter().map(|d| d.worked_minutes).sum(),
+            breaks_minutes: days.iter().map(|d| d.breaks_minutes).sum(),
+            billed_minutes: days.iter().map(|d| d.billed_minutes).sum(),
+            earned: earned.single(),
+            tax: tax.single(),
+            gross: gross.single(),
+            factors: factors
+                .into_iter()
+                .map(|(k, v)| FactorReport {
+                    factor: k,
+                    worked_minutes: v,
+                })
+                .collect(),
+            projects: projects
+                .into_iter()
+                .map(|(k, (worked, earned, tax))| ProjectReport {
+                    project: k.map(String::from),
+                    worked_minutes: worked,
+                    earned: earned.single(),
+                    currencies: CurrencyReport::with_earnings(&earned, &tax),
+                })
+                .collect(),
+            currencies: CurrencyReport::with_earnings(&earned, &tax),
+            days,
+        }
+    }
+}
+
+impl DayReport {
+    fn new(day: &data::Day, rates: &data::Rates) -> DayReport {
+        let earned = day.earned(rates);
+        let tax = day.tax(rates);
+        DayReport {
+            date: day.date,
+            worked_minutes: day.worked().num_minutes(),
+            breaks_minutes: day.breaks().num_minutes(),
+            billed_minutes: day.billed(rates).num_minutes(),
+            earned: earned.single(),
+            tax: tax.single(),
+            currencies: CurrencyReport::with_earnings(&earned, &tax),
+            comment: day.comment.clone(),
+            absence: day.absence,
+            parts: day
+                .parts
+                .iter()
+                .map(|p| PartReport {
+                    start: p.start.format("%H:%M").to_string(),
+                    stop: p.stop.map(|_| p.fmt_stop()),
+                    factor: p.factor.unwrap_or(Decimal::ONE),
+                    project: p.project.clone(),
+                    worked_minutes: p.worked().map(|x| x.num_minutes()),
+                    earned: day.part_earned(p, rates),
+                    tax: day.part_tax(p, rates),
+                    currency: rates.currency_for(p).cloned(),
+                })
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::storage::legacy_parser;
+
+    #[test]
+    fn test_report_with_months() {
+        let l = String::from("2017-05-23   08:00-12:00@acme 13:00-15:00-1.5  # comment");
+        let day = legacy_parser::parse_line(&l).unwrap();
+        let year = data::Year {
+            year: 2017,
+            days: vec![day],
+        };
+        let rates = data::Rates {
+            default: Decimal::new(100, 0),
+            ..Default::default()
+        };
+        let r = Report::with_months(&year.get_months(), &rates);
+
+        assert!(r.years.is_empty());
+        assert_eq!(1, r.months.len());
+        let m = &r.months[0];
+        assert_eq!((2017, Some(5), None), (m.year, m.month, m.week));
+        assert_eq!(360, m.worked_minutes);
+        assert_eq!(60, m.breaks_minutes);
+        assert_eq!(Some(Decimal::new(700, 0)), m.earned);
+        assert_eq!(
+            vec![
+                FactorReport {
+                    factor: Decimal::ONE,
+                    worked_minutes: 240
+                },
+                FactorReport {
+                    factor: Decimal::new(15, 1),
+                    worked_minutes: 120
+                }
+            ],
+            m.factors
+        );
+        assert_eq!(2, m.projects.len());
+        assert_eq!(Some("acme".to_string()), m.projects[1].project);
+
+        let d = &m.days[0];
+        assert_eq!(Some("comment".to_string()), d.comment);
+        assert_eq!("13:00", d.parts[1].start);
+        assert_eq!(Some("15:00".to_string()), d.parts[1].stop);
+        assert_eq!(Some(Decimal::new(300, 0)), d.parts[1].earned);
+    }
+
+    #[test]
+    fn test_report_with_ranges() {
+        let days = ["2024-03-16   08:00-12:00", "2024-04-15   08:00-10:00@acme"]
+            .iter()
+            .map(|l| legacy_parser::parse_line(l).unwrap())
+            .collect::<Vec<_>>();
+        let range = data::Range {
+            from: NaiveDate::from_ymd(2024, 3, 16),
+            to: NaiveDate::from_ymd(2024, 4, 15),
+            days: days.iter().collect(),
+        };
+        let r = Report::with_ranges(&[range], &data::Rates::default());
+
+        assert_eq!(1, r.ranges.len());
+        let p = &r.ranges[0];
+        assert_eq!(
+            (
+                Some(NaiveDate::from_ymd(2024, 3, 16)),
+                Some(NaiveDate::from_ymd(2024, 4, 15))
+            ),
+            (p.from, p.to)
+        );
+        assert_eq!((2, 360), (p.days_recorded, p.worked_minutes));
+        assert_eq!(2, p.projects.len());
+    }
+
+    #[test]
+    fn test_report_currencies() {
+        let days = ["2024-03-04   08:00-10:00 10:00-11:00@globex"]
+            .iter()
+            .map(|l| legacy_parser::parse_line(l).unwrap())
+            .collect::<Vec<_>>();
+        let mut store = data::Storage::default();
+        store.set_rate(None, None, Decimal::new(80, 0), "EUR".parse().ok());
+        store.set_rate(
+            Some("globex".to_string()),
+            None,
+            Decimal::new(90, 0),
+            "USD".parse().ok(),
+        );
+        let days = days.iter().collect::<Vec<_>>();
+        let p = PeriodReport::new(&days, &store.get_rates());
+        assert_eq!((None, None, None), (p.earned, p.tax, p.gross));
+        assert_eq!(2, p.currencies.len());
+        assert_eq!(Some(Decimal::new(160, 0)), p.projects[0].earned);
+        assert_eq!(Some(Decimal::new(90, 0)), p.projects[1].earned);
+
+        let d = &p.days[0];
+        assert_eq!(None, d.earned);
+        assert_eq!(
+            vec![
+                ("EUR".to_string(), Decimal::new(160, 0)),
+                ("USD".to_string(), Decimal::new(90, 0)),
+            ],
+            d.currencies
+                .iter()
+                .map(|c| (c.currency.as_ref().unwrap().to_string(), c.earned))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_format_from_str() {
+        assert_eq!(Ok(Format::Csv), "csv".parse());
+        assert!("xml".parse::<Format>().is_err());
+    }
+}
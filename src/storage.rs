@@ -1,3 +1,8 @@
+pub mod currency;
 pub mod data;
+pub mod decimal;
 //pub mod parsing;
+pub mod holidays;
 pub mod legacy_parser;
+pub mod lock;
+pub mod zone;
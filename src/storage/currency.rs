@@ -0,0 +1,215 @@
+use crate::storage::decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::iter::Sum;
+use std::ops::AddAssign;
+use std::str::FromStr;
+
+/// The symbols of common currencies, other currencies are printed with their code.
+const SYMBOLS: &[(&str, &str)] = &[
+    ("EUR", "€"),
+    ("GBP", "£"),
+    ("INR", "₹"),
+    ("JPY", "¥"),
+    ("KRW", "₩"),
+    ("USD", "$"),
+];
+
+/// The currencies without two decimal places (minor units), as of ISO 4217.
+const DECIMALS: &[(&str, u32)] = &[
+    ("BHD", 3),
+    ("CLP", 0),
+    ("ISK", 0),
+    ("JOD", 3),
+    ("JPY", 0),
+    ("KRW", 0),
+    ("KWD", 3),
+    ("OMR", 3),
+    ("TND", 3),
+    ("VND", 0),
+];
+
+/// A currency given by its ISO 4217 code, e.g. `EUR`.
+#[derive(Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Clone)]
+pub struct Currency(String);
+
+impl FromStr for Currency {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Currency, String> {
+        let s = s.trim();
+        if s.len() != 3 || !s.chars().all(|c| c.is_ascii_alphabetic()) {
+            return Err(format!("Invalid currency code: {}", s));
+        }
+        Ok(Currency(s.to_ascii_uppercase()))
+    }
+}
+
+impl fmt::Display for Currency {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl Currency {
+    pub fn code(&self) -> &str {
+        &self.0
+    }
+
+    /// The number of decimal places amounts are rounded to.
+    pub fn decimals(&self) -> u32 {
+        DECIMALS
+            .iter()
+            .find(|(code, _)| *code == self.0)
+            .map_or(2, |(_, d)| *d)
+    }
+
+    /// Formats the amount with the symbol (or code) and the decimal places of the currency,
+    /// e.g. `€1250.50`, `-$12.00` or `CHF 80.00`.
+    pub fn format(&self, amount: Decimal) -> String {
+        let (sign, amount) = if amount < Decimal::ZERO {
+            ("-", -amount)
+        } else {
+            ("", amount)
+        };
+        let decimals = self.decimals() as usize;
+        match SYMBOLS.iter().find(|(code, _)| *code == self.0) {
+            Some((_, symbol)) => format!("{}{}{:.*}", sign, symbol, decimals, amount),
+            None => format!("{}{} {:.*}", sign, self.0, decimals, amount),
+        }
+    }
+}
+
+/// Returns the decimal places amounts in the given currency are rounded to, two if no currency
+/// has been set.
+pub fn decimals(currency: Option<&Currency>) -> u32 {
+    currency.map_or(2, Currency::decimals)
+}
+
+/// Earned amounts per currency. Amounts are never converted, amounts without a currency (none
+/// has been set) are kept separately.
+#[derive(PartialEq, Eq, Debug, Clone, Default)]
+pub struct Earnings(BTreeMap<Option<Currency>, Decimal>);
+
+impl Earnings {
+    pub fn add(&mut self, currency: Option<&Currency>, amount: Decimal) {
+        *self.0.entry(currency.cloned()).or_default() += amount;
+    }
+
+    /// Returns the amount earned in the given currency.
+    pub fn get(&self, currency: Option<&Currency>) -> Decimal {
+        self.0.get(&currency.cloned()).copied().unwrap_or_default()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (Option<&Currency>, Decimal)> {
+        self.0.iter().map(|(c, a)| (c.as_ref(), *a))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Returns the sum of all amounts regardless of their currency, only meaningful if a single
+    /// currency is used.
+    pub fn total(&self) -> Decimal {
+        self.0.values().copied().sum()
+    }
+
+    /// Returns the amount if at most one currency is used, amounts in different currencies
+    /// can't be summed up.
+    pub fn single(&self) -> Option<Decimal> {
+        if self.0.len() > 1 {
+            return None;
+        }
+        Some(self.total())
+    }
+
+    /// Rounds every amount to the decimal places of its currency.
+    pub fn round(&self) -> Earnings {
+        Earnings(
+            self.0
+                .iter()
+                .map(|(c, a)| (c.clone(), a.round(decimals(c.as_ref()))))
+                .collect(),
+        )
+    }
+}
+
+impl AddAssign for Earnings {
+    fn add_assign(&mut self, other: Earnings) {
+        for (c, a) in other.0 {
+            *self.0.entry(c).or_default() += a;
+        }
+    }
+}
+
+impl Sum for Earnings {
+    fn sum<I: Iterator<Item = Earnings>>(iter: I) -> Earnings {
+        let mut r = Earnings::default();
+        for x in iter {
+            r += x;
+        }
+        r
+    }
+}
+
+impl fmt::Display for Earnings {
+    /// Prints the amount of every currency separated by `, `, e.g. `€120.00, $80.00`.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.0.is_empty() {
+            return write!(f, "{:.2}", Decimal::ZERO);
+        }
+        let s = self
+            .0
+            .iter()
+            .map(|(c, a)| match c {
+                Some(c) => c.format(*a),
+                None => format!("{:.2}", a),
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        write!(f, "{}", s)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_currency_format() {
+        let currency = |s: &str| s.parse::<Currency>().unwrap();
+        assert_eq!("EUR", currency("eur").code());
+        assert_eq!("€1250.50", currency("EUR").format(Decimal::new(12505, 1)));
+        assert_eq!("-$12.00", currency("USD").format(Decimal::new(-12, 0)));
+        assert_eq!("¥1235", currency("JPY").format(Decimal::new(12345, 1)));
+        assert_eq!("CHF 80.00", currency("CHF").format(Decimal::new(80, 0)));
+        assert!("EURO".parse::<Currency>().is_err());
+        assert!("E1R".parse::<Currency>().is_err());
+    }
+
+    #[test]
+    fn test_earnings() {
+        let eur: Currency = "EUR".parse().unwrap();
+        let usd: Currency = "USD".parse().unwrap();
+        let mut a = Earnings::default();
+        assert_eq!("0.00", a.to_string());
+        a.add(Some(&eur), Decimal::new(10, 0));
+        a.add(Some(&usd), Decimal::new(5, 0));
+        let mut b = Earnings::default();
+        b.add(Some(&eur), Decimal::new(25, 1));
+
+        let sum: Earnings = vec![a, b].into_iter().sum();
+        assert_eq!(Decimal::new(125, 1), sum.get(Some(&eur)));
+        assert_eq!(Decimal::new(5, 0), sum.get(Some(&usd)));
+        assert_eq!(Decimal::ZERO, sum.get(None));
+        assert_eq!("€12.50, $5.00", sum.to_string());
+        assert_eq!(None, sum.single());
+        assert_eq!(Some(Decimal::ZERO), Earnings::default().single());
+
+        let mut a = Earnings::default();
+        a.add(Some(&usd), Decimal::new(5, 0));
+        assert_eq!(Some(Decimal::new(5, 0)), a.single());
+    }
+}
@@ -1,37 +1,565 @@
-use rustc_serialize::json;
+use ::serde::de::Error as _;
+use ::serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
 use std::collections::BTreeMap;
-use std::fs::File;
+use std::fmt;
+use std::fs::{self, File};
+use std::io;
 use std::io::prelude::*;
 use std::io::BufReader;
+use std::path::{Path, PathBuf};
 
 use chrono::*;
 
-use crate::legacy_parser;
+use crate::storage::currency::{self, Currency, Earnings};
+use crate::storage::decimal::Decimal;
+use crate::storage::holidays::Calendar;
+use crate::storage::legacy_parser;
+use crate::storage::zone::Zone;
+use crate::Error;
 
-#[derive(RustcDecodable, RustcEncodable, PartialEq, Debug, Default)]
+/// Version of the store format written by reti. Whenever the format changes in a way which
+/// can't be expressed by serde defaults, increase it and add an upgrade step to `upgrade`.
+pub const STORE_VERSION: u32 = 2;
+
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
 pub struct Data {
+    pub version: u32,
     pub years: Vec<Year>,
-    pub fee_per_hour: f32,
+    pub fee_per_hour: Decimal,
+    /// The currency fees are charged in, unless a rate has its own currency.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub currency: Option<Currency>,
+    /// Rates overriding `fee_per_hour` for projects and/or factors.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub rates: Vec<Rate>,
+    /// Fees replacing `fee_per_hour` from a given date on, sorted by date.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub fee_history: Vec<Fee>,
+    /// The contracted working time the overtime balance is calculated against.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target: Option<Target>,
+    /// The vacation allowance in days per year.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vacation_days: Option<u32>,
+    /// Where earned amounts are rounded to cents, per part if not set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rounding: Option<Rounding>,
+    /// How worked time is billed, the exact time if not set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub billing: Option<Billing>,
+    /// The number of the last invoice, invoices are numbered sequentially.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_invoice: Option<u32>,
+    /// Tax (VAT) rates charged on the earned amounts, sorted by date.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub taxes: Vec<Tax>,
+}
+
+/// A fee per hour which is valid from the given date on.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct Fee {
+    pub effective_from: NaiveDate,
+    pub fee: Decimal,
+}
+
+/// An hourly rate for a project and/or factor. If the rate is bound to a factor, the fee
+/// is the absolute rate for parts with that factor, otherwise the part's factor is applied.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct Rate {
+    #[serde(default)]
+    pub project: Option<String>,
+    #[serde(default)]
+    pub factor: Option<Decimal>,
+    pub fee: Decimal,
+    /// The currency of the fee, the currency of the store if not set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub currency: Option<Currency>,
+}
+
+/// A tax (VAT) rate in percent for all parts or the parts of a project (client), valid from
+/// the given date on or always if no date is set.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct Tax {
+    #[serde(default)]
+    pub project: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub effective_from: Option<NaiveDate>,
+    pub rate: Decimal,
+}
+
+impl Tax {
+    fn matches(&self, project: Option<&str>, effective_from: Option<NaiveDate>) -> bool {
+        self.project.as_deref() == project && self.effective_from == effective_from
+    }
+}
+
+/// Where earned amounts are rounded to cents: each part, each day or only a total (e.g. of a
+/// month) when it is shown.
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone, Copy, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum Rounding {
+    #[default]
+    Part,
+    Day,
+    Total,
+}
+
+impl std::str::FromStr for Rounding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Rounding, String> {
+        match s {
+            "part" => Ok(Rounding::Part),
+            "day" => Ok(Rounding::Day),
+            "total" => Ok(Rounding::Total),
+            _ => Err(format!("Unknown rounding: {}", s)),
+        }
+    }
+}
+
+impl fmt::Display for Rounding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            Rounding::Part => "part",
+            Rounding::Day => "day",
+            Rounding::Total => "total",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// The direction worked time is rounded to a billing increment.
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone, Copy, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum RoundingMode {
+    #[default]
+    Up,
+    Down,
+    Nearest,
+}
+
+impl std::str::FromStr for RoundingMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<RoundingMode, String> {
+        match s {
+            "up" => Ok(RoundingMode::Up),
+            "down" => Ok(RoundingMode::Down),
+            "nearest" => Ok(RoundingMode::Nearest),
+            _ => Err(format!("Unknown rounding mode: {}", s)),
+        }
+    }
+}
+
+impl fmt::Display for RoundingMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            RoundingMode::Up => "up",
+            RoundingMode::Down => "down",
+            RoundingMode::Nearest => "nearest",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// Whether each part or the whole day is rounded to the billing increment.
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone, Copy, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum BillingLevel {
+    #[default]
+    Part,
+    Day,
+}
+
+impl std::str::FromStr for BillingLevel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<BillingLevel, String> {
+        match s {
+            "part" => Ok(BillingLevel::Part),
+            "day" => Ok(BillingLevel::Day),
+            _ => Err(format!("Unknown billing level: {}", s)),
+        }
+    }
+}
+
+impl fmt::Display for BillingLevel {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            BillingLevel::Part => "part",
+            BillingLevel::Day => "day",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// How worked time is billed: rounded to increments (e.g. 15 minutes) per part or per day,
+/// with a minimum billable duration. The recorded times are not changed.
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone, Copy)]
+pub struct Billing {
+    /// The increment in minutes, 0 to bill the exact time.
+    pub increment: u32,
+    #[serde(default)]
+    pub mode: RoundingMode,
+    #[serde(default)]
+    pub per: BillingLevel,
+    /// The minimum billable minutes of a part or day.
+    #[serde(default)]
+    pub minimum: u32,
+}
+
+impl Billing {
+    /// Returns the billed minutes for the given worked minutes, nothing worked is not billed.
+    pub fn apply(&self, minutes: i64) -> i64 {
+        if minutes <= 0 {
+            return minutes;
+        }
+        let inc = i64::from(self.increment);
+        let billed = if inc == 0 {
+            minutes
+        } else {
+            match self.mode {
+                RoundingMode::Up => (minutes + inc - 1) / inc * inc,
+                RoundingMode::Down => minutes / inc * inc,
+                RoundingMode::Nearest => (minutes + inc / 2) / inc * inc,
+            }
+        };
+        billed.max(i64::from(self.minimum))
+    }
 }
 
-#[derive(RustcDecodable, RustcEncodable, PartialEq, Debug)]
+impl fmt::Display for Billing {
+    /// E.g. "15min increments, rounded up per part, at least 30min".
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}min increments, rounded {} per {}",
+            self.increment, self.mode, self.per
+        )?;
+        if self.minimum > 0 {
+            write!(f, ", at least {}min", self.minimum)?;
+        }
+        Ok(())
+    }
+}
+
+/// The rate table used for all earned calculations: the default fee, its history, overrides,
+/// the currency, where amounts are rounded and how worked time is billed.
+#[derive(PartialEq, Debug, Clone, Default)]
+pub struct Rates {
+    pub default: Decimal,
+    pub history: Vec<Fee>,
+    pub overrides: Vec<Rate>,
+    pub currency: Option<Currency>,
+    pub rounding: Rounding,
+    pub billing: Option<Billing>,
+    /// Sorted by date.
+    pub taxes: Vec<Tax>,
+}
+
+/// A minimum break rule: a day with more than `after` minutes worked needs breaks of at least
+/// `min` minutes.
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone)]
+pub struct BreakRule {
+    pub after: i64,
+    pub min: i64,
+}
+
+impl BreakRule {
+    /// The rules used if none are configured: 30 minutes after 6h and 45 minutes after 9h.
+    pub fn defaults() -> Vec<BreakRule> {
+        vec![
+            BreakRule {
+                after: 360,
+                min: 30,
+            },
+            BreakRule {
+                after: 540,
+                min: 45,
+            },
+        ]
+    }
+}
+
+/// The expected working time per weekday, valid from the given date on.
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone)]
+pub struct Target {
+    pub effective_from: NaiveDate,
+    /// Expected minutes per weekday, starting with Monday.
+    pub daily: [i64; 7],
+}
+
+impl Target {
+    /// Returns the expected working time from `from` to `to`, both inclusive. No time is
+    /// expected on holidays of the calendar.
+    pub fn expected(&self, from: NaiveDate, to: NaiveDate, holidays: &Calendar) -> Duration {
+        let mut d = Duration::zero();
+        let mut date = from.max(self.effective_from);
+        while date <= to {
+            if holidays.holiday(date).is_none() {
+                d = d + Duration::minutes(
+                    self.daily[date.weekday().num_days_from_monday() as usize],
+                );
+            }
+            date = date.succ();
+        }
+        d
+    }
+}
+
+/// The worked and expected time of a period until (including) the given date.
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct Balance {
+    pub until: NaiveDate,
+    pub worked: Duration,
+    /// The expected time of absence days which are credited as worked.
+    pub credited: Duration,
+    pub expected: Duration,
+}
+
+impl Balance {
+    /// Calculates the balance of the days from `from` to `until`, days before the target is
+    /// effective are not taken into account.
+    fn new<'a, I>(
+        days: I,
+        target: &Target,
+        holidays: &Calendar,
+        from: NaiveDate,
+        until: NaiveDate,
+    ) -> Balance
+    where
+        I: IntoIterator<Item = &'a Day>,
+    {
+        let from = from.max(target.effective_from);
+        let mut worked = Duration::zero();
+        let mut credited = Duration::zero();
+        for d in days {
+            if d.date >= from && d.date <= until {
+                worked = worked + d.worked();
+                // only the time not worked on a partial absence day is credited
+                if d.absence.is_some_and(Absence::is_credited) {
+                    let missing = target.expected(d.date, d.date, holidays) - d.worked();
+                    credited = credited + missing.max(Duration::zero());
+                }
+            }
+        }
+        Balance {
+            until,
+            worked,
+            credited,
+            expected: target.expected(from, until, holidays),
+        }
+    }
+
+    /// Returns the time worked (or credited) more (positive) or less (negative) than expected.
+    pub fn overtime(&self) -> Duration {
+        self.worked + self.credited - self.expected
+    }
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
 pub struct Year {
     pub year: u16,
+    /// Sorted by date, each date at most once.
     pub days: Vec<Day>,
 }
 
-#[derive(RustcDecodable, RustcEncodable, PartialEq, Debug)]
+/// The reason of an absence on a day, for the whole day or, if the day has parts, for the rest
+/// of the expected time.
+#[derive(Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Debug, Clone, Copy)]
+#[serde(rename_all = "kebab-case")]
+pub enum Absence {
+    Vacation,
+    Sick,
+    Holiday,
+    /// Time off in lieu, taken from the overtime balance.
+    CompTime,
+}
+
+impl Absence {
+    /// Whether the expected time of the day not worked is credited, comp time is not.
+    pub fn is_credited(self) -> bool {
+        self != Absence::CompTime
+    }
+}
+
+impl std::str::FromStr for Absence {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Absence, String> {
+        match s {
+            "vacation" => Ok(Absence::Vacation),
+            "sick" => Ok(Absence::Sick),
+            "holiday" => Ok(Absence::Holiday),
+            "comp-time" => Ok(Absence::CompTime),
+            _ => Err(format!("Unknown absence: {}", s)),
+        }
+    }
+}
+
+impl fmt::Display for Absence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            Absence::Vacation => "vacation",
+            Absence::Sick => "sick",
+            Absence::Holiday => "holiday",
+            Absence::CompTime => "comp-time",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
 pub struct Day {
     pub date: NaiveDate,
     pub parts: Vec<Part>,
+    #[serde(default)]
     pub comment: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub absence: Option<Absence>,
 }
 
-#[derive(RustcDecodable, RustcEncodable, PartialEq, Debug, Clone)]
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
 pub struct Part {
     pub start: NaiveTime,
+    #[serde(default)]
     pub stop: Option<NaiveTime>,
-    pub factor: Option<f32>,
+    #[serde(default)]
+    pub factor: Option<Decimal>,
+    /// The project (client) the part has been worked for.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub project: Option<String>,
+    /// The stop is on the day after the start, e.g. for a night shift (22:00-02:00+1). The
+    /// whole part is attributed to the day it started.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub next_day: bool,
+    /// The offset from UTC (in seconds) at the start, see `Zone::resolve`. Together with the
+    /// offset at the stop, it makes durations across a change of daylight saving time correct.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub start_offset: Option<i32>,
+    /// The offset from UTC (in seconds) at the stop.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stop_offset: Option<i32>,
+}
+
+impl Default for Data {
+    fn default() -> Data {
+        Data {
+            version: STORE_VERSION,
+            years: vec![],
+            fee_per_hour: Decimal::ZERO,
+            currency: None,
+            rates: vec![],
+            fee_history: vec![],
+            target: None,
+            vacation_days: None,
+            rounding: None,
+            billing: None,
+            last_invoice: None,
+            taxes: vec![],
+        }
+    }
+}
+
+/// Returns the objects in the list of the given key, none if there is no such list.
+fn json_items<'a>(obj: &'a mut Map<String, Value>, key: &str) -> Vec<&'a mut Map<String, Value>> {
+    match obj.get_mut(key) {
+        Some(Value::Array(items)) => items.iter_mut().filter_map(Value::as_object_mut).collect(),
+        _ => vec![],
+    }
+}
+
+/// Rewrites a decimal written as json number as string, see `Decimal`.
+fn decimal_to_string(value: Option<&mut Value>) {
+    if let Some(value) = value {
+        if let Some(x) = value.as_f64() {
+            *value = Value::from(Decimal::from_f64(x).to_string());
+        }
+    }
+}
+
+/// Upgrades the raw json of a store step by step to `STORE_VERSION`. Returns the version the
+/// store has been upgraded from or `None` if it is already up to date.
+fn upgrade(value: &mut Value) -> Result<Option<u32>, Error> {
+    let obj = match value.as_object_mut() {
+        Some(obj) => obj,
+        None => {
+            let e = serde_json::Error::custom("store is not a json object");
+            return Err(Error::Decode(e));
+        }
+    };
+    let version = obj.get("version").and_then(Value::as_u64).unwrap_or(0) as u32;
+
+    if version > STORE_VERSION {
+        return Err(Error::UnsupportedVersion(version, STORE_VERSION));
+    }
+    if version == STORE_VERSION {
+        return Ok(None);
+    }
+
+    if version < 1 {
+        // stores written by 0.2.0 (rustc-serialize) have no version, lists added later might
+        // have been written as null.
+        for key in &["rates", "fee_history"] {
+            if obj.get(*key).is_some_and(Value::is_null) {
+                obj.remove(*key);
+            }
+        }
+    }
+
+    if version < 2 {
+        // decimals have been written as json numbers (floats), they are strings now
+        decimal_to_string(obj.get_mut("fee_per_hour"));
+        let lists = [
+            ("fee_history", "fee"),
+            ("rates", "fee"),
+            ("rates", "factor"),
+            ("taxes", "rate"),
+        ];
+        for (key, field) in &lists {
+            for item in json_items(obj, key) {
+                decimal_to_string(item.get_mut(*field));
+            }
+        }
+        for year in json_items(obj, "years") {
+            for day in json_items(year, "days") {
+                for part in json_items(day, "parts") {
+                    decimal_to_string(part.get_mut("factor"));
+                }
+            }
+        }
+    }
+
+    obj.insert("version".to_string(), Value::from(STORE_VERSION));
+    Ok(Some(version))
+}
+
+fn backup_path(path: &Path, n: usize) -> PathBuf {
+    let mut p = path.as_os_str().to_owned();
+    p.push(format!(".{}", n));
+    PathBuf::from(p)
+}
+
+/// Shifts the backups of the given file (`file.1` -> `file.2`, ...), dropping the oldest one,
+/// and copies the file to `file.1`.
+fn rotate_backups(path: &Path, backups: usize) -> io::Result<()> {
+    if backups == 0 {
+        return Ok(());
+    }
+
+    let oldest = backup_path(path, backups);
+    if oldest.exists() {
+        fs::remove_file(&oldest)?;
+    }
+    for n in (1..backups).rev() {
+        let from = backup_path(path, n);
+        if from.exists() {
+            fs::rename(&from, backup_path(path, n + 1))?;
+        }
+    }
+    fs::copy(path, backup_path(path, 1))?;
+    Ok(())
 }
 
 impl Year {
@@ -39,32 +567,89 @@ impl Year {
         Year { year, days: vec![] }
     }
 
-    fn get_day(&self, m: u8, d: u8) -> Option<&Day> {
+    /// Returns the index of the day at the given month and day, or the index where it has to
+    /// be inserted to keep the days sorted.
+    fn position(&self, m: u8, d: u8) -> Result<usize, usize> {
+        let key = (u32::from(m), u32::from(d));
         self.days
-            .iter()
-            .find(|ref day| day.date.month() as u8 == m && day.date.day() as u8 == d)
+            .binary_search_by_key(&key, |day| (day.date.month(), day.date.day()))
+    }
+
+    pub fn get_day(&self, m: u8, d: u8) -> Option<&Day> {
+        match self.position(m, d) {
+            Ok(i) => Some(&self.days[i]),
+            Err(_) => None,
+        }
     }
 
     fn get_day_mut(&mut self, m: u8, d: u8) -> Option<&mut Day> {
-        self.days
-            .iter_mut()
-            .find(|ref mut day| day.date.month() as u8 == m && day.date.day() as u8 == d)
+        match self.position(m, d) {
+            Ok(i) => Some(&mut self.days[i]),
+            Err(_) => None,
+        }
+    }
+
+    /// Returns the days of the given month.
+    fn month_days(&self, m: u8) -> &[Day] {
+        let m = u32::from(m);
+        let start = self.days.partition_point(|d| d.date.month() < m);
+        let end = self.days.partition_point(|d| d.date.month() <= m);
+        &self.days[start..end]
     }
 
-    fn add_day(&mut self, day: Day) -> bool {
+    /// Returns the days from `from` to `to` (both inclusive).
+    fn range_days(&self, from: NaiveDate, to: NaiveDate) -> &[Day] {
+        let start = self.days.partition_point(|d| d.date < from);
+        let end = self.days.partition_point(|d| d.date <= to).max(start);
+        &self.days[start..end]
+    }
+
+    /// Inserts the day at its date, unless the date exists already.
+    fn add_day(&mut self, mut day: Day) -> bool {
         let m = day.date.month() as u8;
         let d = day.date.day() as u8;
-        let has_day = self.get_day(m, d).is_some();
-        if !has_day {
-            self.days.push(day);
+        match self.position(m, d) {
+            Err(i) => {
+                day.sort_parts();
+                self.days.insert(i, day);
+                true
+            }
+            Ok(_) => false,
         }
-        !has_day
     }
 
-    pub fn get_months(&self) -> Vec<Month> {
+    /// Sorts the days by date and the parts of each day by start.
+    fn sort(&mut self) {
+        self.days.sort_by_key(|d| d.date);
+        for d in &mut self.days {
+            d.sort_parts();
+        }
+    }
+
+    /// Returns the number of days of the given absence.
+    pub fn absences(&self, absence: Absence) -> usize {
+        self.days
+            .iter()
+            .filter(|d| d.absence == Some(absence))
+            .count()
+    }
+
+    /// Returns the balance of the year until the given date.
+    pub fn balance(&self, target: &Target, holidays: &Calendar, until: NaiveDate) -> Balance {
+        let year = i32::from(self.year);
+        Balance::new(
+            &self.days,
+            target,
+            holidays,
+            NaiveDate::from_ymd(year, 1, 1),
+            until.min(NaiveDate::from_ymd(year, 12, 31)),
+        )
+    }
+
+    pub fn get_months(&self) -> Vec<Month<'_>> {
         let mut months = BTreeMap::<u32, Month>::new();
 
-        for ref d in self.days.iter() {
+        for d in self.days.iter() {
             let m = d.date.month();
             let month = months.entry(m).or_insert_with(|| Month::new(vec![]));
             month.days.push(d);
@@ -80,15 +665,16 @@ impl Day {
             date: day,
             parts: vec![],
             comment: None,
+            absence: None,
         }
     }
 
-    pub fn new_today() -> Day {
-        let today = Utc::today().naive_local();
+    pub fn new_today(zone: &Zone) -> Day {
         Day {
-            date: today,
+            date: zone.today(),
             parts: vec![],
             comment: None,
+            absence: None,
         }
     }
 
@@ -106,78 +692,202 @@ impl Day {
         d
     }
 
-    pub fn earned(&self, fee: f32) -> f32 {
-        let mut result: f32 = 0.0;
+    /// Returns the sum of the gaps between the (sorted) parts of the day.
+    pub fn breaks(&self) -> Duration {
+        let mut parts = self.parts.iter().collect::<Vec<_>>();
+        parts.sort_by_key(|p| p.start);
 
-        for p in &self.parts {
-            let factor = match p.factor {
-                Some(f) => f,
-                _ => 1.0,
-            };
-            result += match p.worked() {
-                Some(worked) => (worked.num_minutes() as f32 / 60.0) * (factor * fee),
-                _ => continue,
+        let mut d = Duration::zero();
+        let mut end: Option<NaiveTime> = None;
+        for p in parts {
+            if let Some(e) = end {
+                if p.start > e {
+                    d = d + (p.start - e);
+                }
+            }
+            end = match (end, p.stop) {
+                // an open part, or one stopping on the next day, lasts until the end of the day
+                (_, None) => break,
+                (_, Some(_)) if p.next_day => break,
+                (Some(e), Some(stop)) if e > stop => Some(e),
+                (_, Some(stop)) => Some(stop),
             };
         }
+        d
+    }
+
+    /// Returns the strictest of the rules which is violated by the breaks of the day, if any.
+    pub fn missing_break<'r>(&self, rules: &'r [BreakRule]) -> Option<&'r BreakRule> {
+        let worked = self.worked().num_minutes();
+        let breaks = self.breaks().num_minutes();
+        rules
+            .iter()
+            .filter(|r| worked > r.after && breaks < r.min)
+            .max_by_key(|r| r.min)
+    }
+
+    /// Returns the billed time of the day: the worked time rounded as a whole if billing is per
+    /// day, the sum of the billed parts otherwise.
+    pub fn billed(&self, rates: &Rates) -> Duration {
+        match rates.billing {
+            Some(b) if b.per == BillingLevel::Day => {
+                Duration::minutes(b.apply(self.worked().num_minutes()))
+            }
+            _ => self
+                .parts
+                .iter()
+                .filter_map(|p| p.billed(rates))
+                .fold(Duration::zero(), |a, b| a + b),
+        }
+    }
+
+    /// The amount earned with the given part of this day. If the day is billed as a whole, the
+    /// billed time is distributed over the parts in proportion to their worked time.
+    pub fn part_earned(&self, part: &Part, rates: &Rates) -> Option<Decimal> {
+        match rates.billing {
+            Some(b) if b.per == BillingLevel::Day => {
+                let worked = part.worked()?.num_minutes();
+                let day = self.worked().num_minutes();
+                if day == 0 {
+                    return Some(Decimal::ZERO);
+                }
+                Some(part.amount(self.date, rates, worked * b.apply(day), day))
+            }
+            _ => part.earned(self.date, rates),
+        }
+    }
+
+    /// Returns the billed hours of the given part of this day. If the day is billed as a whole,
+    /// the billed time is distributed over the parts in proportion to their worked time.
+    pub fn part_billed(&self, part: &Part, rates: &Rates) -> Option<Decimal> {
+        match rates.billing {
+            Some(b) if b.per == BillingLevel::Day => {
+                let worked = part.worked()?.num_minutes();
+                let day = self.worked().num_minutes();
+                if day == 0 {
+                    return Some(Decimal::ZERO);
+                }
+                Some(Decimal::ONE.mul_div(worked * b.apply(day), day * 60))
+            }
+            _ => Some(Decimal::ONE.mul_div(part.billed(rates)?.num_minutes(), 60)),
+        }
+    }
+
+    pub fn earned(&self, rates: &Rates) -> Earnings {
+        let mut result = Earnings::default();
+
+        for p in &self.parts {
+            if let Some(earned) = self.part_earned(p, rates) {
+                result.add(rates.currency_for(p), earned);
+            }
+        }
+        if rates.rounding == Rounding::Day {
+            return result.round();
+        }
         result
     }
 
-    fn does_intersect(&self, part: &Part) -> bool {
+    /// The tax on the amount earned with the given part of this day, at the rate valid for
+    /// the part on this day (see `Rates::tax_for`).
+    pub fn part_tax(&self, part: &Part, rates: &Rates) -> Option<Decimal> {
+        let earned = self.part_earned(part, rates)?;
+        let tax = (earned * rates.tax_for(self.date, part)).mul_div(1, 100);
+        if rates.rounding == Rounding::Part {
+            return Some(tax.round(currency::decimals(rates.currency_for(part))));
+        }
+        Some(tax)
+    }
+
+    /// The tax on the amounts earned on this day, the gross amount is `earned + tax`.
+    pub fn tax(&self, rates: &Rates) -> Earnings {
+        let mut result = Earnings::default();
+
         for p in &self.parts {
-            if p.stop.is_none() || part.stop.is_none() {
-                return true;
+            if let Some(tax) = self.part_tax(p, rates) {
+                result.add(rates.currency_for(p), tax);
             }
+        }
+        if rates.rounding == Rounding::Day {
+            return result.round();
+        }
+        result
+    }
 
-            if part.start > p.start {
-                if p.stop.unwrap() > part.start {
-                    return true;
-                }
-            } else if part.stop.unwrap() > p.start {
-                return true;
+    /// Checks whether the given part overlaps with any part of the day. An open part (no stop)
+    /// is considered to last until the end of the day.
+    fn does_intersect(&self, part: &Part) -> bool {
+        self.parts.iter().any(|p| p.overlaps(part))
+    }
+
+    /// Returns the part of the day which has not been stopped yet, if any.
+    pub fn open_part(&self) -> Option<&Part> {
+        self.parts.iter().find(|p| p.is_open())
+    }
+
+    fn open_part_mut(&mut self) -> Option<&mut Part> {
+        self.parts.iter_mut().find(|p| p.is_open())
+    }
+
+    /// Takes the UTC offsets of the parts which are unchanged in the stored version of this
+    /// day, e.g. after it has been edited as text which has no offsets. A time which occurs
+    /// twice on a change of daylight saving time would not be resolved to the same offset again.
+    pub fn keep_offsets(&mut self, stored: &Day) {
+        for part in &mut self.parts {
+            let same =
+                |p: &&Part| (p.start, p.stop, p.next_day) == (part.start, part.stop, part.next_day);
+            if let Some(p) = stored.parts.iter().find(same) {
+                part.start_offset = p.start_offset;
+                part.stop_offset = p.stop_offset;
             }
         }
-        false
     }
 
-    fn clear_parts(&mut self) {
-        self.parts.clear();
+    /// Keeps the parts of the day sorted by their start.
+    fn sort_parts(&mut self) {
+        self.parts.sort_by_key(|p| p.start);
     }
 
-    fn merge_day(&mut self, other: Day) -> bool {
-        if other.parts.is_empty() {
-            println!("No parts to merge!");
-            return false;
+    /// Merges the parts of the other day into this day. Nothing is merged if any of the parts
+    /// clashes with an existing part or with another part of the other day.
+    fn merge_day(&mut self, other: Day) -> Result<(), Error> {
+        if other.parts.is_empty() && other.absence.is_none() {
+            return Err(Error::NoParts(other.date));
         }
 
         if self.date != other.date {
-            println!(
-                "Nothing to do, not the same day! Self: {} - other: {}",
-                self.date, other.date
-            );
-            return false;
-        }
-
-        let len = self.parts.len();
-        for other_part in other.parts.into_iter() {
-            if !self.does_intersect(&other_part) {
-                self.parts.push(other_part);
-            } else {
-                println!(
-                    "{:?} does clash with existing part in day: {}",
-                    &other_part, self.date
-                );
+            return Err(Error::DateMismatch(self.date, other.date));
+        }
+
+        for (i, part) in other.parts.iter().enumerate() {
+            part.validate()?;
+            if self.does_intersect(part) || other.parts[..i].iter().any(|p| p.overlaps(part)) {
+                return Err(Error::Overlap {
+                    date: self.date,
+                    part: part.clone(),
+                });
             }
         }
+
+        self.parts.extend(other.parts);
+        self.sort_parts();
         if self.comment.is_none() && other.comment.is_some() {
             self.comment = other.comment
         }
-        len != self.parts.len()
+        if self.absence.is_none() {
+            self.absence = other.absence
+        }
+        Ok(())
     }
 
     pub fn as_legacy(&self) -> String {
         format!(
-            "{}   {}{}",
-            self.date.format("%Y-%m-%d").to_string(),
+            "{}   {}{}{}",
+            self.date.format("%Y-%m-%d"),
+            match self.absence {
+                Some(a) if self.parts.is_empty() => format!("!{}", a),
+                Some(a) => format!("!{}  ", a),
+                None => "".to_string(),
+            },
             self.parts
                 .iter()
                 .map(|x| x.as_legacy())
@@ -191,6 +901,33 @@ impl Day {
     }
 }
 
+/// The recorded days of a period, e.g. a week, month, range or any list of days, with their
+/// totals.
+pub trait Period {
+    fn days(&self) -> &[&Day];
+
+    fn worked(&self) -> Duration {
+        self.days()
+            .iter()
+            .fold(Duration::zero(), |a, d| a + d.worked())
+    }
+
+    fn breaks(&self) -> Duration {
+        self.days()
+            .iter()
+            .fold(Duration::zero(), |a, d| a + d.breaks())
+    }
+
+    fn earned(&self, rates: &Rates) -> Earnings {
+        self.days().iter().map(|d| d.earned(rates)).sum()
+    }
+
+    /// The tax on the earned amounts, see `Day::tax`.
+    fn tax(&self, rates: &Rates) -> Earnings {
+        self.days().iter().map(|d| d.tax(rates)).sum()
+    }
+}
+
 pub struct Week<'a> {
     pub days: Vec<&'a Day>,
 }
@@ -200,174 +937,670 @@ impl<'a> Week<'a> {
         Week { days }
     }
 
-    pub fn earned(&self, fee: f32) -> f32 {
-        let mut r = 0.0_f32;
-        for day in &self.days {
-            r += day.earned(fee);
+    /// Returns the ISO week number, e.g. `01`.
+    pub fn as_num(&self) -> String {
+        assert!(!self.days.is_empty());
+        self.days[0].date.format("%V").to_string()
+    }
+
+    /// Returns the ISO year and week, e.g. `2025-W01`.
+    pub fn as_iso(&self) -> String {
+        assert!(!self.days.is_empty());
+        self.days[0].date.format("%G-W%V").to_string()
+    }
+
+    /// Returns the balance of the (whole) week until the given date.
+    pub fn balance(&self, target: &Target, holidays: &Calendar, until: NaiveDate) -> Balance {
+        assert!(!self.days.is_empty());
+        let date = self.days[0].date;
+        let monday = date - Duration::days(i64::from(date.weekday().num_days_from_monday()));
+        let sunday = monday + Duration::days(6);
+        Balance::new(
+            self.days.iter().copied(),
+            target,
+            holidays,
+            monday,
+            until.min(sunday),
+        )
+    }
+}
+
+//#[derive(RustcDecodable, Clone)]
+#[derive(Clone)]
+pub struct Month<'a> {
+    pub days: Vec<&'a Day>,
+}
+
+impl<'a> Month<'a> {
+    fn new(days: Vec<&'a Day>) -> Month<'a> {
+        Month { days }
+    }
+
+    pub fn as_num(&self) -> String {
+        assert!(!self.days.is_empty());
+        self.days[0].date.format("%m").to_string()
+    }
+
+    pub fn as_name(&self) -> String {
+        assert!(!self.days.is_empty());
+        self.days[0].date.format("%B").to_string()
+    }
+
+    /// Returns the balance of the (whole) month until the given date.
+    pub fn balance(&self, target: &Target, holidays: &Calendar, until: NaiveDate) -> Balance {
+        assert!(!self.days.is_empty());
+        let date = self.days[0].date;
+        let first = NaiveDate::from_ymd(date.year(), date.month(), 1);
+        let last = match date.month() {
+            12 => NaiveDate::from_ymd(date.year() + 1, 1, 1),
+            m => NaiveDate::from_ymd(date.year(), m + 1, 1),
         }
-        r
+        .pred();
+        Balance::new(
+            self.days.iter().copied(),
+            target,
+            holidays,
+            first,
+            until.min(last),
+        )
     }
+}
 
-    pub fn worked(&self) -> Duration {
-        let mut d = Duration::zero();
+/// The recorded days of a quarter of a calendar year.
+#[derive(Clone)]
+pub struct Quarter<'a> {
+    pub year: i32,
+    /// 1 based.
+    pub quarter: u32,
+    pub days: Vec<&'a Day>,
+}
+
+impl<'a> Quarter<'a> {
+    pub fn first(&self) -> NaiveDate {
+        NaiveDate::from_ymd(self.year, (self.quarter - 1) * 3 + 1, 1)
+    }
+
+    pub fn last(&self) -> NaiveDate {
+        match self.quarter {
+            4 => NaiveDate::from_ymd(self.year, 12, 31),
+            q => NaiveDate::from_ymd(self.year, q * 3 + 1, 1).pred(),
+        }
+    }
+
+    /// Returns the balance of the (whole) quarter until the given date.
+    pub fn balance(&self, target: &Target, holidays: &Calendar, until: NaiveDate) -> Balance {
+        Balance::new(
+            self.days.iter().copied(),
+            target,
+            holidays,
+            self.first(),
+            until.min(self.last()),
+        )
+    }
+}
+
+/// The recorded days of a fiscal year, which starts at the first day of `start_month` and is
+/// named after the calendar year it starts in.
+#[derive(Clone)]
+pub struct FiscalYear<'a> {
+    pub year: i32,
+    /// 1 based.
+    pub start_month: u32,
+    pub days: Vec<&'a Day>,
+}
+
+impl<'a> FiscalYear<'a> {
+    pub fn first(&self) -> NaiveDate {
+        NaiveDate::from_ymd(self.year, self.start_month, 1)
+    }
+
+    pub fn last(&self) -> NaiveDate {
+        NaiveDate::from_ymd(self.year + 1, self.start_month, 1).pred()
+    }
 
-        for day in &self.days {
-            d = d + day.worked()
+    /// Returns the balance of the (whole) fiscal year until the given date.
+    pub fn balance(&self, target: &Target, holidays: &Calendar, until: NaiveDate) -> Balance {
+        Balance::new(
+            self.days.iter().copied(),
+            target,
+            holidays,
+            self.first(),
+            until.min(self.last()),
+        )
+    }
+}
+
+/// The recorded days from one date to another (both inclusive), e.g. an invoicing period.
+#[derive(Clone)]
+pub struct Range<'a> {
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+    pub days: Vec<&'a Day>,
+}
+
+impl<'a> Range<'a> {
+    /// Returns the balance of the range until the given date.
+    pub fn balance(&self, target: &Target, holidays: &Calendar, until: NaiveDate) -> Balance {
+        Balance::new(
+            self.days.iter().copied(),
+            target,
+            holidays,
+            self.from,
+            until.min(self.to),
+        )
+    }
+}
+
+impl Period for [&Day] {
+    fn days(&self) -> &[&Day] {
+        self
+    }
+}
+
+impl Period for Week<'_> {
+    fn days(&self) -> &[&Day] {
+        &self.days
+    }
+}
+
+impl Period for Month<'_> {
+    fn days(&self) -> &[&Day] {
+        &self.days
+    }
+}
+
+impl Period for Quarter<'_> {
+    fn days(&self) -> &[&Day] {
+        &self.days
+    }
+}
+
+impl Period for FiscalYear<'_> {
+    fn days(&self) -> &[&Day] {
+        &self.days
+    }
+}
+
+impl Period for Range<'_> {
+    fn days(&self) -> &[&Day] {
+        &self.days
+    }
+}
+
+impl Part {
+    fn as_legacy(&self) -> String {
+        format!(
+            "{}-{}-{}{}",
+            self.start.format("%H:%M"),
+            self.fmt_stop(),
+            self.factor.unwrap_or(Decimal::ONE),
+            match self.project {
+                Some(ref p) => format!("@{}", p),
+                None => "".to_string(),
+            }
+        )
+    }
+
+    /// Formats the stop as `HH:MM`, followed by `+1` if it is on the next day, empty for an
+    /// open part.
+    pub fn fmt_stop(&self) -> String {
+        match self.stop {
+            Some(x) if self.next_day => format!("{}+1", x.format("%H:%M")),
+            Some(x) => x.format("%H:%M").to_string(),
+            None => "".to_string(),
+        }
+    }
+
+    pub fn worked(&self) -> Option<Duration> {
+        let mut worked = self.stop?.signed_duration_since(self.start);
+        if self.next_day {
+            worked = worked + Duration::days(1);
+        }
+        if let (Some(start), Some(stop)) = (self.start_offset, self.stop_offset) {
+            worked = worked - Duration::seconds(i64::from(stop - start));
+        }
+        Some(worked)
+    }
+
+    /// Returns the billed time of the part: the worked time rounded if billing is per part.
+    pub fn billed(&self, rates: &Rates) -> Option<Duration> {
+        let worked = self.worked()?;
+        Some(match rates.billing {
+            Some(b) if b.per == BillingLevel::Part => {
+                Duration::minutes(b.apply(worked.num_minutes()))
+            }
+            _ => worked,
+        })
+    }
+
+    /// The amount earned with this part if worked on the given date, in the currency of its
+    /// rate (see `Rates::currency_for`), for its billed time. If the day is billed as a whole,
+    /// see `Day::part_earned`.
+    pub fn earned(&self, date: NaiveDate, rates: &Rates) -> Option<Decimal> {
+        let billed = self.billed(rates)?;
+        Some(self.amount(date, rates, billed.num_minutes(), 1))
+    }
+
+    /// The amount for `num / den` minutes of this part, rounded to cents if the rates round per
+    /// part.
+    fn amount(&self, date: NaiveDate, rates: &Rates, num: i64, den: i64) -> Decimal {
+        let amount = rates.fee_for(date, self).mul_div(num, den * 60);
+        if rates.rounding == Rounding::Part {
+            return amount.round(currency::decimals(rates.currency_for(self)));
+        }
+        amount
+    }
+
+    /// A part is open as long as no stop time has been recorded.
+    pub fn is_open(&self) -> bool {
+        self.stop.is_none()
+    }
+
+    /// Returns start and end of the part in minutes since the beginning of its day, the end of
+    /// a part stopping on the next day is past 24h. Open parts last until the end of the day.
+    fn span(&self) -> (i64, i64) {
+        let minutes = |t: NaiveTime| i64::from(t.num_seconds_from_midnight() / 60);
+        let end = match self.stop {
+            Some(stop) if self.next_day => minutes(stop) + 24 * 60,
+            Some(stop) => minutes(stop),
+            None => 24 * 60,
+        };
+        (minutes(self.start), end)
+    }
+
+    /// Checks that the stop is not before the start and that a part stopping on the next
+    /// day does not last longer than a day.
+    fn validate(&self) -> Result<(), Error> {
+        if let Some(stop) = self.stop {
+            if self.next_day && stop > self.start {
+                return Err(Error::TooLong {
+                    start: self.start,
+                    stop,
+                });
+            }
+            if !self.next_day && stop < self.start {
+                return Err(Error::StopBeforeStart {
+                    start: self.start,
+                    stop,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Two parts of the same day overlap if they share any time, touching parts (e.g.
+    /// 08:00-10:00 and 10:00-12:00) do not. Open parts are treated as lasting until the end of
+    /// the day.
+    fn overlaps(&self, other: &Part) -> bool {
+        let (a_start, a_end) = self.span();
+        let (b_start, b_end) = other.span();
+        a_start < b_end && b_start < a_end
+    }
+
+    /// Checks whether this part, stopping on the next day, overlaps with a part of the next day.
+    fn overlaps_next_day(&self, next: &Part) -> bool {
+        let (_, end) = self.span();
+        let (start, _) = next.span();
+        start < end - 24 * 60
+    }
+}
+
+impl Rate {
+    fn matches(&self, project: Option<&str>, factor: Option<Decimal>) -> bool {
+        self.project.as_deref() == project && self.factor == factor
+    }
+}
+
+impl Rates {
+    /// Returns the default fee valid on the given date.
+    pub fn default_at(&self, date: NaiveDate) -> Decimal {
+        self.history
+            .iter()
+            .rev()
+            .find(|f| f.effective_from <= date)
+            .map_or(self.default, |f| f.fee)
+    }
+
+    fn find(&self, project: Option<&str>, factor: Option<Decimal>) -> Option<&Rate> {
+        self.overrides.iter().find(|r| r.matches(project, factor))
+    }
+
+    /// Returns the rate applying to the given part, `None` if the default fee applies. The most
+    /// specific rate wins: project and factor, project only, factor only.
+    fn rate_for(&self, part: &Part) -> Option<&Rate> {
+        let project = part.project.as_deref();
+        let factor = part.factor.unwrap_or(Decimal::ONE);
+
+        self.find(project, Some(factor))
+            .or_else(|| project.and_then(|p| self.find(Some(p), None)))
+            .or_else(|| self.find(None, Some(factor)))
+    }
+
+    /// Returns the hourly fee for the given part worked on the given date, including its
+    /// factor. Without a rate for the part, the default fee valid on the date is used.
+    pub fn fee_for(&self, date: NaiveDate, part: &Part) -> Decimal {
+        let factor = part.factor.unwrap_or(Decimal::ONE);
+
+        match self.rate_for(part) {
+            Some(r) if r.factor.is_some() => r.fee,
+            Some(r) => r.fee * factor,
+            None => self.default_at(date) * factor,
+        }
+    }
+
+    /// Returns the tax rate in percent for the given part worked on the given date: the rate
+    /// of its project valid on the date, the rate for all parts otherwise, 0 without any.
+    pub fn tax_for(&self, date: NaiveDate, part: &Part) -> Decimal {
+        let valid = |project: Option<&str>| {
+            self.taxes
+                .iter()
+                .rev()
+                .find(|t| t.project.as_deref() == project && t.effective_from <= Some(date))
+                .map(|t| t.rate)
+        };
+        part.project
+            .as_deref()
+            .and_then(|p| valid(Some(p)))
+            .or_else(|| valid(None))
+            .unwrap_or_default()
+    }
+
+    /// Returns the currency the given part is charged in: the currency of its rate, the
+    /// currency of the store otherwise.
+    pub fn currency_for(&self, part: &Part) -> Option<&Currency> {
+        self.rate_for(part)
+            .and_then(|r| r.currency.as_ref())
+            .or(self.currency.as_ref())
+    }
+}
+
+impl fmt::Display for Part {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.as_legacy())
+    }
+}
+
+#[derive(Default)]
+pub struct Storage {
+    data: Data,
+    upgraded_from: Option<u32>,
+}
+
+impl Storage {
+    pub fn from_file(file: &str) -> Result<Storage, Error> {
+        let mut file = File::open(file)?;
+        let mut s = String::new();
+        file.read_to_string(&mut s)?;
+
+        Storage::from_json(&s)
+    }
+
+    /// Decodes a store from json, stores of older versions are upgraded on the fly.
+    pub fn from_json(s: &str) -> Result<Storage, Error> {
+        let mut value: Value = serde_json::from_str(s)?;
+        let upgraded_from = upgrade(&mut value)?;
+
+        let mut data: Data = serde_json::from_value(value)?;
+        // stores written by older versions are not sorted
+        data.years.sort_by_key(|y| y.year);
+        for y in &mut data.years {
+            y.sort();
+        }
+        Ok(Storage {
+            data,
+            upgraded_from,
+        })
+    }
+
+    /// The version the store has been upgraded from while loading, if any.
+    pub fn upgraded_from(&self) -> Option<u32> {
+        self.upgraded_from
+    }
+
+    pub fn set_fee(&mut self, fee: Decimal) {
+        self.data.fee_per_hour = fee
+    }
+
+    pub fn get_rates(&self) -> Rates {
+        Rates {
+            default: self.data.fee_per_hour,
+            history: self.data.fee_history.clone(),
+            overrides: self.data.rates.clone(),
+            currency: self.data.currency.clone(),
+            rounding: self.data.rounding.unwrap_or_default(),
+            billing: self.data.billing,
+            taxes: self.data.taxes.clone(),
+        }
+    }
+
+    /// Sets the fee valid from today on: the base fee as long as there is no fee history,
+    /// a new entry of the history otherwise, so that the days before keep their fee.
+    pub fn set_current_fee(&mut self, today: NaiveDate, fee: Decimal) {
+        if self.data.fee_history.is_empty() {
+            self.set_fee(fee);
+        } else {
+            self.set_fee_from(today, fee);
+        }
+    }
+
+    /// Sets the fee which is valid from the given date on, an existing fee for the same date
+    /// will be replaced. Days before the first entry use the base fee (see `set_fee`).
+    pub fn set_fee_from(&mut self, effective_from: NaiveDate, fee: Decimal) {
+        let history = &mut self.data.fee_history;
+        history.retain(|f| f.effective_from != effective_from);
+        history.push(Fee {
+            effective_from,
+            fee,
+        });
+        history.sort_by_key(|f| f.effective_from);
+    }
+
+    /// Sets the currency of the fees (and of all rates without a currency).
+    pub fn set_currency(&mut self, currency: Currency) {
+        self.data.currency = Some(currency);
+    }
+
+    /// Sets the rate for the given project and/or factor, without both the default fee is set.
+    /// A rate without a currency is charged in the currency of the store.
+    pub fn set_rate(
+        &mut self,
+        project: Option<String>,
+        factor: Option<Decimal>,
+        fee: Decimal,
+        currency: Option<Currency>,
+    ) {
+        if project.is_none() && factor.is_none() {
+            self.set_fee(fee);
+            if let Some(currency) = currency {
+                self.set_currency(currency);
+            }
+            return;
+        }
+
+        let rates = &mut self.data.rates;
+        match rates
+            .iter_mut()
+            .find(|r| r.matches(project.as_deref(), factor))
+        {
+            Some(r) => {
+                r.fee = fee;
+                r.currency = currency;
+            }
+            None => rates.push(Rate {
+                project,
+                factor,
+                fee,
+                currency,
+            }),
         }
-        d
     }
 
-    pub fn as_num(&self) -> String {
-        assert!(!self.days.is_empty());
-        self.days[0].date.format("%W").to_string()
+    /// Removes the rate for the given project and/or factor.
+    pub fn remove_rate(&mut self, project: Option<&str>, factor: Option<Decimal>) -> bool {
+        let len = self.data.rates.len();
+        self.data.rates.retain(|r| !r.matches(project, factor));
+        len != self.data.rates.len()
     }
-}
-
-//#[derive(RustcDecodable, Clone)]
-#[derive(Clone)]
-pub struct Month<'a> {
-    pub days: Vec<&'a Day>,
-}
 
-impl<'a> Month<'a> {
-    fn new(days: Vec<&'a Day>) -> Month<'a> {
-        Month { days }
+    /// Sets the tax rate for the given project (all parts without one) valid from the given
+    /// date on (always without one), an existing rate for the same project and date will be
+    /// replaced.
+    pub fn set_tax(
+        &mut self,
+        project: Option<String>,
+        effective_from: Option<NaiveDate>,
+        rate: Decimal,
+    ) {
+        let taxes = &mut self.data.taxes;
+        taxes.retain(|t| !t.matches(project.as_deref(), effective_from));
+        taxes.push(Tax {
+            project,
+            effective_from,
+            rate,
+        });
+        taxes.sort_by_key(|t| t.effective_from);
     }
 
-    pub fn worked(&self) -> Duration {
-        let mut d = Duration::zero();
-
-        for day in &self.days {
-            d = d + day.worked()
-        }
-        d
+    /// Removes the tax rate for the given project and date.
+    pub fn remove_tax(&mut self, project: Option<&str>, effective_from: Option<NaiveDate>) -> bool {
+        let len = self.data.taxes.len();
+        self.data
+            .taxes
+            .retain(|t| !t.matches(project, effective_from));
+        len != self.data.taxes.len()
     }
 
-    pub fn earned(&self, fee: f32) -> f32 {
-        let mut r = 0.0_f32;
-        for day in &self.days {
-            r += day.earned(fee);
-        }
-        r
+    pub fn set_rounding(&mut self, rounding: Rounding) {
+        self.data.rounding = Some(rounding);
     }
 
-    pub fn as_num(&self) -> String {
-        assert!(!self.days.is_empty());
-        self.days[0].date.format("%m").to_string()
+    /// Returns the number the next invoice will get.
+    pub fn peek_invoice_number(&self) -> u32 {
+        self.data.last_invoice.unwrap_or(0) + 1
     }
 
-    pub fn as_name(&self) -> String {
-        assert!(!self.days.is_empty());
-        self.days[0].date.format("%B").to_string()
+    /// Returns the number for a new invoice, the counter is saved with the store.
+    pub fn next_invoice_number(&mut self) -> u32 {
+        let number = self.peek_invoice_number();
+        self.data.last_invoice = Some(number);
+        number
     }
-}
 
-impl Part {
-    fn as_legacy(&self) -> String {
-        format!(
-            "{}-{}-{}",
-            self.start.format("%H:%M").to_string(),
-            match self.stop {
-                Some(x) => x.format("%H:%M").to_string(),
-                _ => "".to_string(),
-            },
-            self.factor.unwrap_or(1.0)
-        )
+    /// Sets how worked time is billed, `None` to bill the exact time.
+    pub fn set_billing(&mut self, billing: Option<Billing>) {
+        self.data.billing = billing;
     }
 
-    pub fn worked(&self) -> Option<Duration> {
-        self.stop?;
-        Some(self.stop.unwrap().signed_duration_since(self.start))
+    pub fn get_target(&self) -> Option<&Target> {
+        self.data.target.as_ref()
     }
-}
 
-#[derive(Default)]
-pub struct Storage {
-    data: Data,
-}
+    pub fn set_target(&mut self, target: Target) {
+        self.data.target = Some(target);
+    }
 
-impl Storage {
-    pub fn from_file(file: &str) -> Result<Storage, json::DecoderError> {
-        let mut file = File::open(file).unwrap();
-        let mut s = String::new();
-        let _ = file.read_to_string(&mut s).unwrap();
+    pub fn get_vacation_days(&self) -> Option<u32> {
+        self.data.vacation_days
+    }
 
-        let data = json::decode(&s)?;
-        Ok(Storage { data })
+    pub fn set_vacation_days(&mut self, days: u32) {
+        self.data.vacation_days = Some(days);
     }
 
-    pub fn set_fee(&mut self, fee: f32) {
-        self.data.fee_per_hour = fee
+    /// Returns the first recorded day, if any.
+    pub fn first_day(&self) -> Option<NaiveDate> {
+        self.data
+            .years
+            .iter()
+            .flat_map(|y| y.days.iter())
+            .map(|d| d.date)
+            .next()
     }
 
-    pub fn get_fee(&self) -> f32 {
-        self.data.fee_per_hour
+    /// Returns the cumulative balance from the date the target is effective until the given
+    /// date, `None` if no target has been set.
+    pub fn balance(&self, holidays: &Calendar, until: NaiveDate) -> Option<Balance> {
+        let target = self.data.target.as_ref()?;
+        let days = self.data.years.iter().flat_map(|y| y.days.iter());
+        Some(Balance::new(
+            days,
+            target,
+            holidays,
+            target.effective_from,
+            until,
+        ))
     }
 
-    pub fn import_legacy(&mut self, file: &str) -> bool {
-        let f = match File::open(file) {
-            Ok(x) => x,
-            _ => return false,
-        };
-        let f = BufReader::new(f);
-
-        for line in f.lines() {
-            let line = match line {
-                Ok(l) => l,
-                Err(e) => {
-                    println!("Unable to process line: {}", e);
-                    continue;
-                }
-            };
-            println!("line: {}", line);
+    /// Imports all days of the given legacy file. Lines which can't be parsed or added are
+    /// skipped, the returned list contains the reason for each of them.
+    pub fn import_legacy(&mut self, file: &str) -> Result<Vec<Error>, Error> {
+        let f = BufReader::new(File::open(file)?);
+        let mut skipped = vec![];
+
+        for (i, line) in f.lines().enumerate() {
+            let line = line?;
             match legacy_parser::parse_line(&line) {
                 Ok(day) => {
-                    self.add_day(day);
+                    if let Err(e) = self.add_day(day) {
+                        skipped.push(e);
+                    }
                 }
-                Err(e) => match e {
-                    legacy_parser::ParserError::IgnoreLine => println!("Line as been ignored"),
-                    legacy_parser::ParserError::EmptyLine => println!("Line is empty"),
-                    legacy_parser::ParserError::DayParseError => println!("DayParseError"),
-                },
+                Err(legacy_parser::ParserError::IgnoreLine)
+                | Err(legacy_parser::ParserError::EmptyLine) => (),
+                Err(error) => skipped.push(Error::Parse { line: i + 1, error }),
             }
         }
 
-        true
+        Ok(skipped)
     }
 
-    pub fn save(&self, file: &str, readable: bool) -> bool {
-        let mut f = File::create(file).unwrap();
+    /// Saves the store to the given file. The data is written to a temporary file in the same
+    /// directory first which then replaces the store, hence the store is never left truncated.
+    /// The previous content of the store is kept in `backups` rotating backups (`file.1` being
+    /// the most recent one).
+    pub fn save(&self, file: &str, readable: bool, backups: usize) -> Result<(), Error> {
+        let path = Path::new(file);
+        let dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
 
-        if readable {
-            let encoded = json::as_pretty_json(&self.data);
-            write!(f, "{}", encoded).is_ok()
+        let encoded = if readable {
+            serde_json::to_vec_pretty(&self.data)?
         } else {
-            let encoded = json::as_json(&self.data);
-            write!(f, "{}", encoded).is_ok()
+            serde_json::to_vec(&self.data)?
+        };
+
+        let mut tmp = tempfile::NamedTempFile::new_in(dir)?;
+        tmp.write_all(&encoded)?;
+        if let Ok(meta) = fs::metadata(path) {
+            tmp.as_file().set_permissions(meta.permissions())?;
         }
-    }
+        tmp.as_file().sync_all()?;
 
-    pub fn get_week(&self, y: u16, w: u32) -> Option<Week> {
-        if let Some(year) = self.get_year(y) {
-            let days: Vec<&Day> = year
-                .days
-                .iter()
-                .filter(|&x| x.date.iso_week().week() == w)
-                .collect();
-            if !days.is_empty() {
-                return Some(Week::new(days));
-            }
+        if path.exists() {
+            rotate_backups(path, backups)?;
         }
-        None
+        tmp.persist(path).map_err(|e| Error::Io(e.error))?;
+
+        // make the rename itself durable, not supported on every platform
+        if let Ok(d) = File::open(dir) {
+            let _ = d.sync_all();
+        }
+        Ok(())
+    }
+
+    /// Returns the given ISO week of the ISO year, which can contain days of the previous or
+    /// the next calendar year, e.g. 2025-W01 starts at 2024-12-30.
+    pub fn get_week(&self, y: u16, w: u32) -> Option<Week<'_>> {
+        let monday = NaiveDate::from_isoywd_opt(i32::from(y), w, Weekday::Mon)?;
+        let range = self.get_range(monday, monday + Duration::days(6))?;
+        Some(Week::new(range.days))
     }
 
-    pub fn get_month(&self, y: u16, m: u8) -> Option<Month> {
+    pub fn get_month(&self, y: u16, m: u8) -> Option<Month<'_>> {
         if let Some(year) = self.get_year(y) {
-            let days: Vec<&Day> = year
-                .days
-                .iter()
-                .filter(|&x| x.date.month() == u32::from(m))
-                .collect();
+            let days: Vec<&Day> = year.month_days(m).iter().collect();
             if !days.is_empty() {
                 return Some(Month::new(days));
             }
@@ -375,45 +1608,104 @@ impl Storage {
         None
     }
 
-    pub fn get_day(&self, y: u16, m: u8, d: u8) -> Option<&Day> {
-        if let Some(month) = self.get_month(y, m) {
-            if let Some(day) = month.days.iter().find(|&&x| x.date.day() == d as u32) {
-                return Some(day);
-            }
+    /// Returns the recorded days from `from` to `to` (both inclusive) sorted by date, `None` if
+    /// there are none.
+    pub fn get_range(&self, from: NaiveDate, to: NaiveDate) -> Option<Range<'_>> {
+        let days: Vec<&Day> = self
+            .data
+            .years
+            .iter()
+            .filter(|y| i32::from(y.year) >= from.year() && i32::from(y.year) <= to.year())
+            .flat_map(|y| y.range_days(from, to))
+            .collect();
+        if days.is_empty() {
+            return None;
         }
-        None
+        Some(Range { from, to, days })
+    }
+
+    /// Returns the given quarter (1 to 4) of the year, `None` if no day has been recorded.
+    pub fn get_quarter(&self, y: u16, q: u32) -> Option<Quarter<'_>> {
+        if !(1..=4).contains(&q) {
+            return None;
+        }
+        let mut quarter = Quarter {
+            year: i32::from(y),
+            quarter: q,
+            days: vec![],
+        };
+        quarter.days = self.get_range(quarter.first(), quarter.last())?.days;
+        Some(quarter)
+    }
+
+    /// Returns the fiscal year starting in the given month (1 to 12) of the year, `None` if no
+    /// day has been recorded.
+    pub fn get_fiscal_year(&self, y: u16, start_month: u32) -> Option<FiscalYear<'_>> {
+        if !(1..=12).contains(&start_month) {
+            return None;
+        }
+        let mut fy = FiscalYear {
+            year: i32::from(y),
+            start_month,
+            days: vec![],
+        };
+        fy.days = self.get_range(fy.first(), fy.last())?.days;
+        Some(fy)
+    }
+
+    pub fn get_day(&self, y: u16, m: u8, d: u8) -> Option<&Day> {
+        self.get_year(y)?.get_day(m, d)
+    }
+
+    /// Returns a day from the store based chrono::NaiveDate
+    pub fn get_day_nd(&self, date: NaiveDate) -> Option<&Day> {
+        self.get_day(date.year() as u16, date.month() as u8, date.day() as u8)
     }
 
     /// Removes a day from the store based chrono::NaiveDate
     pub fn remove_day_nd(&mut self, date: NaiveDate) -> bool {
         let year = self.get_year_mut(date.year() as u16);
-        let size = year.days.len();
-        year.days.retain(|x| x.date != date);
-        size > year.days.len()
+        match year.position(date.month() as u8, date.day() as u8) {
+            Ok(i) => {
+                year.days.remove(i);
+                true
+            }
+            Err(_) => false,
+        }
     }
 
-    pub fn get_year(&self, y: u16) -> Option<&Year> {
-        self.data.years.iter().find(|&x| x.year == y)
+    /// Returns all recorded years.
+    pub fn get_years(&self) -> &[Year] {
+        &self.data.years
     }
 
-    fn get_year_mut(&mut self, y: u16) -> &mut Year {
-        let find = |y: u16| self.data.years.iter().find(|&year| year.year == y);
-        if find(y).is_none() {
-            let year = Year::new(y);
-            self.data.years.push(year);
+    pub fn get_year(&self, y: u16) -> Option<&Year> {
+        match self.data.years.binary_search_by_key(&y, |x| x.year) {
+            Ok(i) => Some(&self.data.years[i]),
+            Err(_) => None,
         }
+    }
 
-        self.data
-            .years
-            .iter_mut()
-            .find(|ref mut year| year.year == y)
-            .unwrap()
+    /// Returns the given year, which is inserted (sorted by year) if it does not exist yet.
+    fn get_year_mut(&mut self, y: u16) -> &mut Year {
+        let i = match self.data.years.binary_search_by_key(&y, |x| x.year) {
+            Ok(i) => i,
+            Err(i) => {
+                self.data.years.insert(i, Year::new(y));
+                i
+            }
+        };
+        &mut self.data.years[i]
     }
 
-    pub fn add_part(&mut self, date: NaiveDate, part: Part) -> bool {
-        if part.stop.is_some() && part.stop.unwrap() < part.start {
-            println!("Well, did you stopped working before you started?");
-            return false;
+    pub fn add_part(&mut self, date: NaiveDate, part: Part) -> Result<(), Error> {
+        part.validate()?;
+        self.check_adjacent_days(date, std::slice::from_ref(&part))?;
+
+        if part.is_open() {
+            if let Some((d, p)) = self.open_parts().first() {
+                return Err(Error::AlreadyRunning(*d, p.start));
+            }
         }
 
         let y = date.year() as u16;
@@ -427,39 +1719,220 @@ impl Storage {
         if let Some(day) = year.get_day_mut(m, d) {
             return day.merge_day(new_day);
         }
-        year.add_day(new_day)
+        year.add_day(new_day);
+        Ok(())
+    }
+
+    /// Checks the parts to be added at the given date against the parts of the previous day
+    /// which stop on this day and against the parts of the next day.
+    fn check_adjacent_days(&self, date: NaiveDate, parts: &[Part]) -> Result<(), Error> {
+        let get = |d: NaiveDate| self.get_day(d.year() as u16, d.month() as u8, d.day() as u8);
+
+        if let Some(prev) = get(date.pred()) {
+            for p in parts {
+                if prev.parts.iter().any(|x| x.overlaps_next_day(p)) {
+                    return Err(Error::Overlap {
+                        date,
+                        part: p.clone(),
+                    });
+                }
+            }
+        }
+        if let Some(next) = get(date.succ()) {
+            for p in parts {
+                if next.parts.iter().any(|x| p.overlaps_next_day(x)) {
+                    return Err(Error::Overlap {
+                        date,
+                        part: p.clone(),
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns all parts in the store which have not been stopped yet, together with their date.
+    pub fn open_parts(&self) -> Vec<(NaiveDate, &Part)> {
+        self.data
+            .years
+            .iter()
+            .flat_map(|y| y.days.iter())
+            .filter_map(|d| d.open_part().map(|p| (d.date, p)))
+            .collect()
+    }
+
+    /// Returns a copy of the store which only contains the parts of the given project. Days
+    /// without any part of the project are dropped, absence days as well. Hence the copy has
+    /// no target, a balance of the project's time against it would be meaningless.
+    pub fn with_project(&self, project: &str) -> Storage {
+        let mut data = self.data.clone();
+        data.target = None;
+        for year in &mut data.years {
+            for day in &mut year.days {
+                day.parts.retain(|p| p.project.as_deref() == Some(project));
+            }
+            year.days.retain(|d| !d.parts.is_empty());
+        }
+        data.years.retain(|y| !y.days.is_empty());
+        Storage {
+            data,
+            upgraded_from: None,
+        }
+    }
+
+    /// Starts the given (open) part at the given date. Only one part may be running at a time.
+    pub fn start_part(
+        &mut self,
+        date: NaiveDate,
+        part: Part,
+        comment: Option<String>,
+    ) -> Result<(), Error> {
+        if !part.is_open() {
+            return Err(Error::AlreadyStopped);
+        }
+
+        if let Some((d, p)) = self.open_parts().first() {
+            return Err(Error::AlreadyRunning(*d, p.start));
+        }
+
+        let mut day = Day::new(date);
+        day.parts.push(part);
+        day.comment = comment;
+        self.add_day(day)
+    }
+
+    /// Stops the single open part in the store at the given date and time, the offset from UTC
+    /// (in seconds) at the stop is stored if given.
+    pub fn stop_part(
+        &mut self,
+        date: NaiveDate,
+        stop: NaiveTime,
+        offset: Option<i32>,
+    ) -> Result<(), Error> {
+        let (d, start) = match self.open_parts().as_slice() {
+            [] => return Err(Error::NotRunning),
+            [(d, p)] => (*d, p.start),
+            _ => return Err(Error::MultipleRunning),
+        };
+
+        // a part started yesterday can be stopped after midnight
+        let next_day = d.succ() == date && stop <= start;
+        if d != date && !next_day {
+            return Err(Error::RunningOnOtherDay(d));
+        }
+
+        if stop < start && !next_day {
+            return Err(Error::StopBeforeStart { start, stop });
+        }
+
+        let year = self.get_year_mut(d.year() as u16);
+        let day = year.get_day_mut(d.month() as u8, d.day() as u8).unwrap();
+        let mut part = day.open_part().unwrap().clone();
+        part.stop = Some(stop);
+        part.next_day = next_day;
+        part.stop_offset = offset;
+        self.check_adjacent_days(d, std::slice::from_ref(&part))?;
+
+        let year = self.get_year_mut(d.year() as u16);
+        let day = year.get_day_mut(d.month() as u8, d.day() as u8).unwrap();
+        *day.open_part_mut().unwrap() = part;
+        Ok(())
     }
 
-    pub fn add_day(&mut self, day: Day) -> bool {
+    pub fn add_day(&mut self, day: Day) -> Result<(), Error> {
         let y = day.date.year() as u16;
         let m = day.date.month() as u8;
         let d = day.date.day() as u8;
 
+        for p in &day.parts {
+            p.validate()?;
+        }
+        self.check_adjacent_days(day.date, &day.parts)?;
         let year = self.get_year_mut(y);
         if let Some(existing_day) = year.get_day_mut(m, d) {
             return existing_day.merge_day(day);
         }
-        year.add_day(day)
+        year.add_day(day);
+        Ok(())
     }
 
-    pub fn add_day_force(&mut self, day: Day) -> bool {
+    /// Adds the day, replacing the parts and comment of the day if it exists already.
+    pub fn add_day_force(&mut self, day: Day) -> Result<(), Error> {
         let y = day.date.year() as u16;
         let m = day.date.month() as u8;
         let d = day.date.day() as u8;
 
-        if day.parts.is_empty() {
-            println!("No parts specified!");
-            return false;
-        }
+        let mut new_day = Day::new(day.date);
+        new_day.merge_day(day)?;
+        self.check_adjacent_days(new_day.date, &new_day.parts)?;
 
         let year = self.get_year_mut(y);
         if let Some(existing_day) = year.get_day_mut(m, d) {
-            existing_day.clear_parts();
-            existing_day.comment = None;
-            return existing_day.merge_day(day);
+            *existing_day = new_day;
+            return Ok(());
         }
-        year.add_day(day)
+        year.add_day(new_day);
+        Ok(())
+    }
+}
+
+#[test]
+fn test_storage_from_json_upgrade() {
+    // written by reti 0.2.0 using rustc-serialize
+    let s = r#"{"years":[{"year":2016,"days":[{"date":"2016-08-27","parts":[
+        {"start":"12:00:00","stop":"16:00:00","factor":2.0}],"comment":null}]}],
+        "fee_per_hour":50.0}"#;
+    let store = Storage::from_json(s).unwrap();
+    assert_eq!(Some(0), store.upgraded_from());
+    assert_eq!(STORE_VERSION, store.data.version);
+    assert_eq!(Decimal::new(50, 0), store.get_rates().default);
+    let day = store.get_day(2016, 8, 27).unwrap();
+    assert_eq!(Decimal::new(400, 0), day.earned(&store.get_rates()).total());
+
+    let s = serde_json::to_string(&store.data).unwrap();
+    assert!(s.contains(r#""fee_per_hour":"50""#));
+    assert!(s.contains(r#""factor":"2""#));
+    let store = Storage::from_json(&s).unwrap();
+    assert_eq!(None, store.upgraded_from());
+
+    // written by reti with decimals as json numbers
+    let s = r#"{"version":1,"years":[],"fee_per_hour":80.5,
+        "rates":[{"project":"acme","factor":1.5,"fee":90.25}]}"#;
+    let mut value: Value = serde_json::from_str(s).unwrap();
+    assert_eq!(Some(1), upgrade(&mut value).unwrap());
+    assert_eq!("80.5", value["fee_per_hour"]);
+    assert_eq!("1.5", value["rates"][0]["factor"]);
+    assert_eq!("90.25", value["rates"][0]["fee"]);
+    assert_eq!(Some(1), Storage::from_json(s).unwrap().upgraded_from());
+
+    let s = format!(
+        r#"{{"version":{},"years":[],"fee_per_hour":0}}"#,
+        STORE_VERSION + 1
+    );
+    assert!(Storage::from_json(&s).is_err());
+}
+
+#[test]
+fn test_storage_save_backups() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("store.json");
+    let file = path.to_str().unwrap();
+
+    let mut store = Storage::default();
+    for fee in 1..=4 {
+        store.set_fee(Decimal::new(fee, 0));
+        store.save(file, false, 2).unwrap();
     }
+
+    let fee = |p: &Path| {
+        let store = Storage::from_file(p.to_str().unwrap()).unwrap();
+        store.get_rates().default
+    };
+    assert_eq!(Decimal::new(4, 0), fee(&path));
+    assert_eq!(Decimal::new(3, 0), fee(&backup_path(&path, 1)));
+    assert_eq!(Decimal::new(2, 0), fee(&backup_path(&path, 2)));
+    assert!(!backup_path(&path, 3).exists());
+    assert_eq!(3, fs::read_dir(dir.path()).unwrap().count());
 }
 
 #[test]
@@ -478,20 +1951,581 @@ fn test_day_worked() {
     assert_eq!(worked.num_minutes(), 90 + 300);
 }
 
+#[test]
+fn test_day_breaks() {
+    let l = String::from("2017-05-23     13:00-15:00 08:00-12:00 09:00-10:00 15:30-");
+    let day = legacy_parser::parse_line(&l).unwrap();
+    assert_eq!(90, day.breaks().num_minutes());
+
+    let l = String::from("2017-05-23     08:00-12:00 12:00-13:00");
+    let day = legacy_parser::parse_line(&l).unwrap();
+    assert_eq!(0, day.breaks().num_minutes());
+}
+
+#[test]
+fn test_day_missing_break() {
+    let rules = BreakRule::defaults();
+
+    let l = String::from("2017-05-23     08:00-14:00");
+    let day = legacy_parser::parse_line(&l).unwrap();
+    assert!(day.missing_break(&rules).is_none());
+
+    let l = String::from("2017-05-23     08:00-12:00 12:15-14:30");
+    let day = legacy_parser::parse_line(&l).unwrap();
+    assert_eq!(Some(&rules[0]), day.missing_break(&rules));
+
+    let l = String::from("2017-05-23     08:00-12:00 12:30-18:00");
+    let day = legacy_parser::parse_line(&l).unwrap();
+    assert_eq!(Some(&rules[1]), day.missing_break(&rules));
+
+    let l = String::from("2017-05-23     08:00-12:00 12:45-18:00");
+    let day = legacy_parser::parse_line(&l).unwrap();
+    assert!(day.missing_break(&rules).is_none());
+}
+
+#[test]
+fn test_storage_get_range() {
+    let mut store = Storage::default();
+    for l in &[
+        "2024-04-16     08:00-12:00",
+        "2023-12-31     08:00-09:00",
+        "2024-03-16     08:00-10:00",
+        "2024-04-15     08:00-11:00",
+        "2024-03-15     08:00-16:00",
+    ] {
+        store
+            .add_day(legacy_parser::parse_line(l).unwrap())
+            .unwrap();
+    }
+
+    let range = store
+        .get_range(
+            NaiveDate::from_ymd(2024, 3, 16),
+            NaiveDate::from_ymd(2024, 4, 15),
+        )
+        .unwrap();
+    let dates = range.days.iter().map(|d| d.date).collect::<Vec<_>>();
+    assert_eq!(
+        vec![
+            NaiveDate::from_ymd(2024, 3, 16),
+            NaiveDate::from_ymd(2024, 4, 15)
+        ],
+        dates
+    );
+    assert_eq!(300, range.worked().num_minutes());
+
+    let range = store
+        .get_range(
+            NaiveDate::from_ymd(2023, 12, 1),
+            NaiveDate::from_ymd(2024, 3, 15),
+        )
+        .unwrap();
+    assert_eq!(2, range.days.len());
+    assert!(store
+        .get_range(
+            NaiveDate::from_ymd(2024, 1, 1),
+            NaiveDate::from_ymd(2024, 3, 1)
+        )
+        .is_none());
+}
+
+#[test]
+fn test_storage_get_quarter() {
+    let mut store = Storage::default();
+    for l in &[
+        "2024-03-31     08:00-12:00",
+        "2024-04-01     08:00-10:00",
+        "2024-12-31     08:00-09:00",
+        "2025-03-31     08:00-11:00",
+        "2025-04-01     08:00-16:00",
+    ] {
+        store
+            .add_day(legacy_parser::parse_line(l).unwrap())
+            .unwrap();
+    }
+
+    let q = store.get_quarter(2024, 2).unwrap();
+    assert_eq!(NaiveDate::from_ymd(2024, 6, 30), q.last());
+    assert_eq!(1, q.days.len());
+    let q = store.get_quarter(2024, 4).unwrap();
+    assert_eq!(60, q.worked().num_minutes());
+    assert!(store.get_quarter(2024, 3).is_none());
+    assert!(store.get_quarter(2024, 5).is_none());
+
+    let fy = store.get_fiscal_year(2024, 4).unwrap();
+    assert_eq!(
+        (
+            NaiveDate::from_ymd(2024, 4, 1),
+            NaiveDate::from_ymd(2025, 3, 31)
+        ),
+        (fy.first(), fy.last())
+    );
+    assert_eq!(3, fy.days.len());
+    assert_eq!(360, fy.worked().num_minutes());
+    assert_eq!(3, store.get_fiscal_year(2024, 1).unwrap().days.len());
+    assert!(store.get_fiscal_year(2024, 13).is_none());
+}
+
+#[test]
+fn test_storage_sorted() {
+    let s = r#"{"version":1,"fee_per_hour":0.0,"years":[
+        {"year":2018,"days":[{"date":"2018-01-02","parts":[],"comment":null}]},
+        {"year":2017,"days":[
+            {"date":"2017-05-24","parts":[{"start":"08:00:00","stop":"09:00:00","factor":null}],
+             "comment":null},
+            {"date":"2017-05-22","parts":[
+                {"start":"13:00:00","stop":"14:00:00","factor":null},
+                {"start":"08:00:00","stop":"09:00:00","factor":null}],"comment":null}]}]}"#;
+    let mut store = Storage::from_json(s).unwrap();
+    let dates = |store: &Storage| {
+        store
+            .get_years()
+            .iter()
+            .flat_map(|y| y.days.iter().map(|d| d.date.to_string()))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        vec!["2017-05-22", "2017-05-24", "2018-01-02"],
+        dates(&store)
+    );
+    let day = store.get_day(2017, 5, 22).unwrap();
+    assert_eq!(NaiveTime::from_hms(8, 0, 0), day.parts[0].start);
+
+    for l in &[
+        "2017-05-23     10:00-11:00",
+        "2016-12-31     10:00-11:00",
+        "2017-05-21     10:00-11:00",
+    ] {
+        store
+            .add_day(legacy_parser::parse_line(l).unwrap())
+            .unwrap();
+    }
+    store
+        .add_part(
+            NaiveDate::from_ymd(2017, 5, 23),
+            legacy_parser::parse_part("08:00-09:00").unwrap(),
+        )
+        .unwrap();
+    assert_eq!(
+        vec![
+            "2016-12-31",
+            "2017-05-21",
+            "2017-05-22",
+            "2017-05-23",
+            "2017-05-24",
+            "2018-01-02"
+        ],
+        dates(&store)
+    );
+    let day = store.get_day(2017, 5, 23).unwrap();
+    assert_eq!(NaiveTime::from_hms(8, 0, 0), day.parts[0].start);
+    assert_eq!(Some(NaiveDate::from_ymd(2016, 12, 31)), store.first_day());
+    assert_eq!(4, store.get_month(2017, 5).unwrap().days.len());
+    assert!(store.get_day(2017, 2, 30).is_none());
+
+    assert!(store.remove_day_nd(NaiveDate::from_ymd(2017, 5, 22)));
+    assert!(!store.remove_day_nd(NaiveDate::from_ymd(2017, 5, 22)));
+    assert!(store.get_day(2017, 5, 22).is_none());
+}
+
+#[test]
+fn test_storage_get_week_iso() {
+    let mut store = Storage::default();
+    for l in &[
+        "2025-01-06     08:00-12:00",
+        "2024-12-31     08:00-09:00",
+        "2025-01-01     08:00-10:00",
+        "2024-12-30     08:00-11:00",
+        "2021-01-03     08:00-16:00",
+        "2020-12-31     08:00-16:00",
+    ] {
+        store
+            .add_day(legacy_parser::parse_line(l).unwrap())
+            .unwrap();
+    }
+
+    let week = store.get_week(2025, 1).unwrap();
+    let dates = week.days.iter().map(|d| d.date).collect::<Vec<_>>();
+    assert_eq!(
+        vec![
+            NaiveDate::from_ymd(2024, 12, 30),
+            NaiveDate::from_ymd(2024, 12, 31),
+            NaiveDate::from_ymd(2025, 1, 1)
+        ],
+        dates
+    );
+    assert_eq!(("01", "2025-W01"), (&*week.as_num(), &*week.as_iso()));
+    assert!(store.get_week(2024, 1).is_none());
+
+    let week = store.get_week(2020, 53).unwrap();
+    assert_eq!(2, week.days.len());
+    assert_eq!("2020-W53", week.as_iso());
+    assert!(store.get_week(2021, 53).is_none());
+}
+
+#[test]
+fn test_storage_balance() {
+    let mut store = Storage::default();
+    assert!(store
+        .balance(&Calendar::default(), NaiveDate::from_ymd(2017, 5, 31))
+        .is_none());
+
+    // Mon 2017-05-22 until Wed 2017-05-24, Sun 2017-05-28 is not expected
+    for l in &[
+        "2017-05-19     08:00-18:00",
+        "2017-05-22     08:00-17:00",
+        "2017-05-23     08:00-16:00",
+        "2017-05-24     08:00-14:00",
+        "2017-05-28     10:00-12:00",
+    ] {
+        store
+            .add_day(legacy_parser::parse_line(l).unwrap())
+            .unwrap();
+    }
+    store.set_target(Target {
+        effective_from: NaiveDate::from_ymd(2017, 5, 22),
+        daily: [480, 480, 480, 480, 480, 0, 0],
+    });
+
+    let b = store
+        .balance(&Calendar::default(), NaiveDate::from_ymd(2017, 5, 24))
+        .unwrap();
+    assert_eq!(23 * 60, b.worked.num_minutes());
+    assert_eq!(24 * 60, b.expected.num_minutes());
+    assert_eq!(-60, b.overtime().num_minutes());
+
+    let target = store.get_target().unwrap();
+    let week = store.get_week(2017, 21).unwrap();
+    let b = week.balance(
+        target,
+        &Calendar::default(),
+        NaiveDate::from_ymd(2017, 6, 30),
+    );
+    assert_eq!(NaiveDate::from_ymd(2017, 5, 28), b.until);
+    assert_eq!(40 * 60, b.expected.num_minutes());
+    assert_eq!(-15 * 60, b.overtime().num_minutes());
+
+    let month = store.get_month(2017, 5).unwrap();
+    let b = month.balance(
+        target,
+        &Calendar::default(),
+        NaiveDate::from_ymd(2017, 5, 23),
+    );
+    assert_eq!(17 * 60, b.worked.num_minutes());
+    assert_eq!(16 * 60, b.expected.num_minutes());
+}
+
+#[test]
+fn test_storage_balance_holidays() {
+    let mut store = Storage::default();
+    for l in &[
+        "2017-04-13     08:00-16:00",
+        "2017-04-14     08:00-10:00",
+        "2017-04-17     !holiday",
+    ] {
+        store
+            .add_day(legacy_parser::parse_line(l).unwrap())
+            .unwrap();
+    }
+    store.set_target(Target {
+        effective_from: NaiveDate::from_ymd(2017, 4, 13),
+        daily: [480, 480, 480, 480, 480, 0, 0],
+    });
+    // Good Friday and Easter Monday
+    let holidays = Calendar::from_toml(
+        "[[holiday]]\nname = \"Good Friday\"\neaster = -2\n\
+         [[holiday]]\nname = \"Easter Monday\"\neaster = 1\n",
+    )
+    .unwrap();
+
+    let b = store
+        .balance(&holidays, NaiveDate::from_ymd(2017, 4, 17))
+        .unwrap();
+    assert_eq!(8 * 60, b.expected.num_minutes());
+    assert_eq!(0, b.credited.num_minutes());
+    assert_eq!(2 * 60, b.overtime().num_minutes());
+}
+
+#[test]
+fn test_storage_balance_absence() {
+    let mut store = Storage::default();
+    for l in &[
+        "2017-05-22     08:00-17:00",
+        "2017-05-23     !vacation",
+        "2017-05-24     !comp-time",
+        "2017-05-25     !sick  08:00-10:00",
+    ] {
+        store
+            .add_day(legacy_parser::parse_line(l).unwrap())
+            .unwrap();
+    }
+    store.set_target(Target {
+        effective_from: NaiveDate::from_ymd(2017, 5, 22),
+        daily: [480, 480, 480, 480, 480, 0, 0],
+    });
+
+    let b = store
+        .balance(&Calendar::default(), NaiveDate::from_ymd(2017, 5, 25))
+        .unwrap();
+    assert_eq!(11 * 60, b.worked.num_minutes());
+    assert_eq!(14 * 60, b.credited.num_minutes());
+    assert_eq!(-7 * 60, b.overtime().num_minutes());
+    assert_eq!(1, store.get_year(2017).unwrap().absences(Absence::Vacation));
+}
+
 #[test]
 fn test_day_earned() {
-    let fee = 100_f32;
+    let fee = Rates {
+        default: Decimal::new(100, 0),
+        ..Default::default()
+    };
     let l = String::from("2017-05-23     10:00-12:00");
     let d = legacy_parser::parse_line(&l).unwrap();
 
-    let earned = d.earned(fee);
-    assert_eq!(200_f32, earned);
+    let earned = d.earned(&fee).total();
+    assert_eq!(Decimal::new(200, 0), earned);
 
     let l = String::from("2017-05-24     10:00-11:00-0.5   13:00-14:00-2.0");
     let d = legacy_parser::parse_line(&l).unwrap();
 
-    let earned = d.earned(fee);
-    assert_eq!(250_f32, earned);
+    let earned = d.earned(&fee).total();
+    assert_eq!(Decimal::new(250, 0), earned);
+}
+
+#[test]
+fn test_day_earned_fee_history() {
+    let mut store = Storage::default();
+    store.set_fee(Decimal::new(100, 0));
+    store.set_fee_from(NaiveDate::from_ymd(2017, 7, 1), Decimal::new(60, 0));
+    store.set_fee_from(NaiveDate::from_ymd(2017, 3, 1), Decimal::new(50, 0));
+    let rates = store.get_rates();
+    assert_eq!(2, rates.history.len());
+
+    let earned = |l: &str| legacy_parser::parse_line(l).unwrap().earned(&rates).total();
+    assert_eq!(Decimal::new(100, 0), earned("2017-02-28   10:00-11:00"));
+    assert_eq!(Decimal::new(50, 0), earned("2017-03-01   10:00-11:00"));
+    assert_eq!(Decimal::new(50, 0), earned("2017-06-30   10:00-11:00"));
+    assert_eq!(Decimal::new(90, 0), earned("2017-07-01   10:00-11:00-1.5"));
+
+    store.set_fee_from(NaiveDate::from_ymd(2017, 7, 1), Decimal::new(70, 0));
+    let rates = store.get_rates();
+    assert_eq!(2, rates.history.len());
+    assert_eq!(
+        Decimal::new(70, 0),
+        rates.default_at(NaiveDate::from_ymd(2018, 1, 1))
+    );
+}
+
+#[test]
+fn test_storage_set_current_fee() {
+    let mut store = Storage::default();
+    store.set_current_fee(NaiveDate::from_ymd(2017, 1, 1), Decimal::new(100, 0));
+    let rates = store.get_rates();
+    assert_eq!(Decimal::new(100, 0), rates.default);
+    assert!(rates.history.is_empty());
+
+    store.set_fee_from(NaiveDate::from_ymd(2017, 3, 1), Decimal::new(50, 0));
+    store.set_current_fee(NaiveDate::from_ymd(2017, 7, 1), Decimal::new(70, 0));
+    let rates = store.get_rates();
+    assert_eq!(Decimal::new(100, 0), rates.default);
+    assert_eq!(2, rates.history.len());
+    let at = |y, m, d| rates.default_at(NaiveDate::from_ymd(y, m, d));
+    assert_eq!(Decimal::new(100, 0), at(2017, 2, 28));
+    assert_eq!(Decimal::new(50, 0), at(2017, 6, 30));
+    assert_eq!(Decimal::new(70, 0), at(2017, 7, 1));
+}
+
+#[test]
+fn test_day_tax() {
+    let mut store = Storage::default();
+    store.set_fee(Decimal::new(100, 0));
+    store.set_tax(None, None, Decimal::new(19, 0));
+    store.set_tax(
+        None,
+        Some(NaiveDate::from_ymd(2020, 7, 1)),
+        Decimal::new(16, 0),
+    );
+    store.set_tax(
+        None,
+        Some(NaiveDate::from_ymd(2021, 1, 1)),
+        Decimal::new(19, 0),
+    );
+    store.set_tax(Some("acme".to_string()), None, Decimal::new(7, 0));
+    store.set_tax(
+        None,
+        Some(NaiveDate::from_ymd(2020, 7, 1)),
+        Decimal::new(16, 0),
+    );
+    let rates = store.get_rates();
+    assert_eq!(4, rates.taxes.len());
+
+    let tax = |l: &str| legacy_parser::parse_line(l).unwrap().tax(&rates).total();
+    assert_eq!(Decimal::new(19, 0), tax("2020-06-30   10:00-11:00"));
+    assert_eq!(Decimal::new(16, 0), tax("2020-12-31   10:00-11:00"));
+    assert_eq!(Decimal::new(19, 0), tax("2021-01-01   10:00-11:00"));
+    assert_eq!(Decimal::new(7, 0), tax("2020-12-31   10:00-11:00@acme"));
+    // 50min at 100 is 83.33, the tax is rounded per part
+    assert_eq!(Decimal::new(1583, 2), tax("2021-01-01   10:00-10:50"));
+
+    assert!(store.remove_tax(Some("acme"), None));
+    assert!(!store.remove_tax(Some("acme"), None));
+    let rates = store.get_rates();
+    let day = legacy_parser::parse_line("2020-12-31   10:00-11:00@acme").unwrap();
+    assert_eq!(Decimal::new(16, 0), rates.tax_for(day.date, &day.parts[0]));
+}
+
+#[test]
+fn test_day_earned_rates() {
+    let mut store = Storage::default();
+    store.set_rate(None, None, Decimal::new(100, 0), None);
+    store.set_rate(Some("acme".to_string()), None, Decimal::new(80, 0), None);
+    store.set_rate(None, Some(Decimal::new(2, 0)), Decimal::new(150, 0), None);
+    store.set_rate(
+        Some("acme".to_string()),
+        Some(Decimal::new(15, 1)),
+        Decimal::new(90, 0),
+        None,
+    );
+    let rates = store.get_rates();
+    assert_eq!(Decimal::new(100, 0), rates.default);
+    assert_eq!(3, rates.overrides.len());
+
+    let earned = |l: &str| legacy_parser::parse_line(l).unwrap().earned(&rates).total();
+    assert_eq!(Decimal::new(100, 0), earned("2017-05-24   10:00-11:00"));
+    assert_eq!(Decimal::new(80, 0), earned("2017-05-24   10:00-11:00@acme"));
+    assert_eq!(
+        Decimal::new(40, 0),
+        earned("2017-05-24   10:00-11:00-0.5@acme")
+    );
+    assert_eq!(
+        Decimal::new(90, 0),
+        earned("2017-05-24   10:00-11:00-1.5@acme")
+    );
+    assert_eq!(
+        Decimal::new(160, 0),
+        earned("2017-05-24   10:00-11:00-2@acme")
+    );
+    assert_eq!(Decimal::new(150, 0), earned("2017-05-24   10:00-11:00-2"));
+
+    assert!(store.remove_rate(Some("acme"), None));
+    assert!(!store.remove_rate(Some("acme"), None));
+    assert_eq!(2, store.get_rates().overrides.len());
+}
+
+#[test]
+fn test_day_earned_rounding() {
+    let mut store = Storage::default();
+    store.set_fee(Decimal::new(80, 0));
+    // 50min at 80 per hour are 66.666...
+    let l = "2017-05-24   08:00-08:50 09:00-09:50 10:00-10:50";
+    let day = legacy_parser::parse_line(l).unwrap();
+
+    assert_eq!(Rounding::Part, store.get_rates().rounding);
+    let earned = |store: &Storage| day.earned(&store.get_rates()).total();
+    assert_eq!(Decimal::new(20001, 2), earned(&store));
+    store.set_rounding(Rounding::Day);
+    assert_eq!(Decimal::new(200, 0), earned(&store));
+    store.set_rounding(Rounding::Total);
+    assert_eq!(Decimal::new(2_000_001, 4), earned(&store));
+    assert_eq!("200.00", day.earned(&store.get_rates()).to_string());
+
+    assert_eq!(Ok(Rounding::Day), "day".parse());
+    assert!("week".parse::<Rounding>().is_err());
+}
+
+#[test]
+fn test_billing_apply() {
+    let mut b = Billing {
+        increment: 15,
+        mode: RoundingMode::Up,
+        per: BillingLevel::Part,
+        minimum: 0,
+    };
+    assert_eq!(0, b.apply(0));
+    assert_eq!(15, b.apply(1));
+    assert_eq!(15, b.apply(15));
+    assert_eq!(30, b.apply(16));
+    b.mode = RoundingMode::Down;
+    assert_eq!(15, b.apply(29));
+    b.minimum = 30;
+    assert_eq!(30, b.apply(14));
+    assert_eq!(0, b.apply(0));
+
+    let b = Billing {
+        increment: 6,
+        mode: RoundingMode::Nearest,
+        per: BillingLevel::Part,
+        minimum: 0,
+    };
+    assert_eq!(6, b.apply(8));
+    assert_eq!(12, b.apply(9));
+}
+
+#[test]
+fn test_day_earned_billing() {
+    let mut store = Storage::default();
+    store.set_fee(Decimal::new(60, 0));
+    let l = "2017-05-24   08:00-08:20 09:00-09:50";
+    let day = legacy_parser::parse_line(l).unwrap();
+    let mut billing = Billing {
+        increment: 15,
+        mode: RoundingMode::Up,
+        per: BillingLevel::Part,
+        minimum: 0,
+    };
+
+    store.set_billing(Some(billing));
+    let rates = store.get_rates();
+    assert_eq!(70, day.worked().num_minutes());
+    assert_eq!(90, day.billed(&rates).num_minutes());
+    assert_eq!(Decimal::new(90, 0), day.earned(&rates).total());
+
+    // 75min billed are distributed over the parts
+    billing.per = BillingLevel::Day;
+    store.set_billing(Some(billing));
+    let rates = store.get_rates();
+    assert_eq!(75, day.billed(&rates).num_minutes());
+    assert_eq!(
+        Some(Decimal::new(2143, 2)),
+        day.part_earned(&day.parts[0], &rates)
+    );
+    assert_eq!(Decimal::new(75, 0), day.earned(&rates).total());
+
+    billing.minimum = 120;
+    store.set_billing(Some(billing));
+    assert_eq!(Decimal::new(120, 0), day.earned(&store.get_rates()).total());
+    assert_eq!(70, day.worked().num_minutes());
+}
+
+#[test]
+fn test_day_earned_currencies() {
+    let eur: Currency = "EUR".parse().unwrap();
+    let usd: Currency = "USD".parse().unwrap();
+    let jpy: Currency = "JPY".parse().unwrap();
+    let mut store = Storage::default();
+    store.set_rate(None, None, Decimal::new(100, 0), Some(eur.clone()));
+    store.set_rate(
+        Some("acme".to_string()),
+        None,
+        Decimal::new(80, 0),
+        Some(usd.clone()),
+    );
+    store.set_rate(None, Some(Decimal::new(2, 0)), Decimal::new(150, 0), None);
+    store.set_rate(
+        Some("tokyo".to_string()),
+        None,
+        Decimal::new(1000, 0),
+        Some(jpy.clone()),
+    );
+    let rates = store.get_rates();
+
+    let l = "2017-05-24   08:00-09:00 09:00-10:00@acme 10:00-11:00-2@acme 11:00-11:50@tokyo";
+    let earned = legacy_parser::parse_line(l).unwrap().earned(&rates);
+    assert_eq!(Decimal::new(100, 0), earned.get(Some(&eur)));
+    assert_eq!(Decimal::new(240, 0), earned.get(Some(&usd)));
+    // yen have no decimal places
+    assert_eq!(Decimal::new(833, 0), earned.get(Some(&jpy)));
+    assert_eq!("€100.00, ¥833, $240.00", earned.to_string());
 }
 
 #[test]
@@ -517,6 +2551,176 @@ fn test_day_does_intersect() {
     assert!(!day.does_intersect(&part));
 }
 
+#[test]
+fn test_day_does_intersect_open_part() {
+    let l = String::from("2017-05-23     08:00-12:00");
+    let mut day = legacy_parser::parse_line(&l).unwrap();
+
+    let open = |h| Part {
+        start: NaiveTime::from_hms(h, 0, 0),
+        stop: None,
+        factor: None,
+        project: None,
+        next_day: false,
+        start_offset: None,
+        stop_offset: None,
+    };
+    assert!(day.does_intersect(&open(9)));
+    assert!(!day.does_intersect(&open(12)));
+
+    day.parts.push(open(13));
+    let part = legacy_parser::parse_part("12:00-13:00").unwrap();
+    assert!(!day.does_intersect(&part));
+    let part = legacy_parser::parse_part("12:00-14:00").unwrap();
+    assert!(day.does_intersect(&part));
+    let part = legacy_parser::parse_part("15:00-16:00").unwrap();
+    assert!(day.does_intersect(&part));
+}
+
+#[test]
+fn test_storage_start_stop_part() {
+    let mut store = Storage::default();
+    let date = NaiveDate::from_ymd(2017, 5, 23);
+
+    assert!(matches!(
+        store.stop_part(date, NaiveTime::from_hms(12, 0, 0), None),
+        Err(Error::NotRunning)
+    ));
+    let part = legacy_parser::parse_part("08:00-").unwrap();
+    assert!(store.start_part(date, part, None).is_ok());
+    let part = legacy_parser::parse_part("09:00-").unwrap();
+    assert!(matches!(
+        store.start_part(date, part, None),
+        Err(Error::AlreadyRunning(..))
+    ));
+    assert_eq!(1, store.open_parts().len());
+
+    assert!(matches!(
+        store.stop_part(date, NaiveTime::from_hms(7, 0, 0), None),
+        Err(Error::StopBeforeStart { .. })
+    ));
+    assert!(matches!(
+        store.stop_part(date.succ(), NaiveTime::from_hms(12, 0, 0), None),
+        Err(Error::RunningOnOtherDay(_))
+    ));
+    assert!(store
+        .stop_part(date, NaiveTime::from_hms(12, 0, 0), None)
+        .is_ok());
+    assert!(store.open_parts().is_empty());
+
+    let day = store.get_day(2017, 5, 23).unwrap();
+    assert_eq!(240, day.worked().num_minutes());
+}
+
+#[test]
+fn test_part_next_day() {
+    let part = legacy_parser::parse_part("22:00-02:30+1").unwrap();
+    assert!(part.next_day);
+    assert_eq!(Some(270), part.worked().map(|x| x.num_minutes()));
+    assert_eq!("22:00-02:30+1-1", part.as_legacy());
+    assert!(part.validate().is_ok());
+    let part = legacy_parser::parse_part("02:00-03:00+1").unwrap();
+    assert!(matches!(part.validate(), Err(Error::TooLong { .. })));
+
+    let l = String::from("2017-05-23     08:00-12:00 21:00-01:00+1");
+    let day = legacy_parser::parse_line(&l).unwrap();
+    assert_eq!(480, day.worked().num_minutes());
+    assert_eq!(540, day.breaks().num_minutes());
+    let part = legacy_parser::parse_part("23:00-23:30").unwrap();
+    assert!(day.does_intersect(&part));
+
+    let mut store = Storage::default();
+    assert!(store.add_day(day).is_ok());
+    let next = NaiveDate::from_ymd(2017, 5, 24);
+    let part = legacy_parser::parse_part("00:30-08:00").unwrap();
+    assert!(matches!(
+        store.add_part(next, part),
+        Err(Error::Overlap { .. })
+    ));
+    let part = legacy_parser::parse_part("01:00-08:00").unwrap();
+    assert!(store.add_part(next, part).is_ok());
+    let prev = NaiveDate::from_ymd(2017, 5, 22);
+    let part = legacy_parser::parse_part("23:00-08:30+1").unwrap();
+    assert!(matches!(
+        store.add_part(prev, part),
+        Err(Error::Overlap { .. })
+    ));
+}
+
+#[test]
+fn test_storage_stop_part_next_day() {
+    let mut store = Storage::default();
+    let date = NaiveDate::from_ymd(2017, 5, 23);
+
+    let part = legacy_parser::parse_part("22:00-").unwrap();
+    assert!(store.start_part(date, part, None).is_ok());
+    assert!(store
+        .stop_part(date.succ(), NaiveTime::from_hms(2, 0, 0), None)
+        .is_ok());
+    let day = store.get_day(2017, 5, 23).unwrap();
+    assert!(day.parts[0].next_day);
+    assert_eq!(240, day.worked().num_minutes());
+}
+
+#[test]
+fn test_day_as_legacy() {
+    let l = String::from("2017-05-23   08:00-12:00-1.5@acme  13:00--1   # comment");
+    let day = legacy_parser::parse_line(&l).unwrap();
+    assert_eq!(l, day.as_legacy());
+    assert_eq!(day, legacy_parser::parse_line(&day.as_legacy()).unwrap());
+
+    let l = String::from("2017-05-23   !holiday");
+    let day = legacy_parser::parse_line(&l).unwrap();
+    assert_eq!(l, day.as_legacy());
+
+    let l = String::from("2017-05-23   !sick  08:00-10:00-1   # comment");
+    let day = legacy_parser::parse_line(&l).unwrap();
+    assert_eq!(l, day.as_legacy());
+}
+
+#[test]
+fn test_day_keep_offsets() {
+    let mut stored = legacy_parser::parse_line("2017-10-29   01:30-02:30  03:00-04:00").unwrap();
+    stored.parts[0].start_offset = Some(7200);
+    stored.parts[0].stop_offset = Some(3600);
+    stored.parts[1].start_offset = Some(3600);
+    stored.parts[1].stop_offset = Some(3600);
+
+    let mut day = legacy_parser::parse_line("2017-10-29   01:30-02:30  03:00-05:00").unwrap();
+    day.keep_offsets(&stored);
+    assert_eq!(Some(7200), day.parts[0].start_offset);
+    assert_eq!(Some(3600), day.parts[0].stop_offset);
+    assert_eq!(None, day.parts[1].start_offset);
+    assert_eq!(None, day.parts[1].stop_offset);
+}
+
+#[test]
+fn test_storage_with_project() {
+    let mut store = Storage::default();
+    let l = String::from("2017-05-23     08:00-12:00@acme 13:00-15:00");
+    store
+        .add_day(legacy_parser::parse_line(&l).unwrap())
+        .unwrap();
+    let l = String::from("2017-05-24     08:00-12:00@other");
+    store
+        .add_day(legacy_parser::parse_line(&l).unwrap())
+        .unwrap();
+
+    store.set_target(Target {
+        effective_from: NaiveDate::from_ymd(2017, 5, 1),
+        daily: [480, 480, 480, 480, 480, 0, 0],
+    });
+
+    let acme = store.with_project("acme");
+    let day = acme.get_day(2017, 5, 23).unwrap();
+    assert_eq!(1, day.parts.len());
+    assert_eq!(240, day.worked().num_minutes());
+    assert!(acme.get_day(2017, 5, 24).is_none());
+    assert!(acme.get_target().is_none());
+
+    assert!(store.with_project("none").get_year(2017).is_none());
+}
+
 #[test]
 fn test_day_merge_day() {
     let l = String::from("2017-05-23     08:00-12:00");
@@ -527,19 +2731,25 @@ fn test_day_merge_day() {
     // not the same day!
     let l = String::from("2017-08-05     14:00-16:00");
     let other = legacy_parser::parse_line(&l).unwrap();
-    assert!(!day.merge_day(other));
+    assert!(matches!(day.merge_day(other), Err(Error::DateMismatch(..))));
     assert_eq!(1, day.parts.len());
 
     // does intersect!
     let l = String::from("2017-05-23     09:00-10:00");
     let other = legacy_parser::parse_line(&l).unwrap();
-    assert!(!day.merge_day(other));
+    assert!(matches!(day.merge_day(other), Err(Error::Overlap { .. })));
     assert_eq!(1, day.parts.len());
 
+    // an absence without parts
+    let l = String::from("2017-05-23     !sick");
+    let other = legacy_parser::parse_line(&l).unwrap();
+    assert!(day.merge_day(other).is_ok());
+    assert_eq!(Some(Absence::Sick), day.absence);
+
     // shall work
     let l = String::from("2017-05-23     14:00-15:00");
     let other = legacy_parser::parse_line(&l).unwrap();
-    assert!(day.merge_day(other));
+    assert!(day.merge_day(other).is_ok());
     assert_eq!(2, day.parts.len());
 }
 
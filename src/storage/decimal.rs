@@ -0,0 +1,294 @@
+use serde::de::{self, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::convert::TryFrom;
+use std::fmt;
+use std::iter::Sum;
+use std::ops::{Add, AddAssign, Mul, Neg, Sub};
+use std::str::FromStr;
+
+/// The number of decimal places stored.
+const PLACES: u32 = 4;
+const SCALE: i64 = 10_000;
+
+/// A fixed-point decimal number with four decimal places, used for fees, factors and earned
+/// amounts instead of floats, so sums do not drift. In the store it is written as a string,
+/// e.g. `"80.5"`, json numbers written by older versions can still be read.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Clone, Copy, Default)]
+pub struct Decimal(i64);
+
+/// Converts a result calculated with more bits back, saturating at the bounds of `i64`.
+fn saturate(v: i128) -> Decimal {
+    Decimal(i64::try_from(v).unwrap_or(if v < 0 { i64::MIN } else { i64::MAX }))
+}
+
+/// Divides and rounds half away from zero.
+fn div_round(n: i128, d: i128) -> i128 {
+    let q = n / d;
+    let r = n % d;
+    if 2 * r.abs() >= d.abs() {
+        q + n.signum() * d.signum()
+    } else {
+        q
+    }
+}
+
+impl Decimal {
+    pub const ZERO: Decimal = Decimal(0);
+    pub const ONE: Decimal = Decimal(SCALE);
+
+    /// Creates `num * 10^-scale`, e.g. `Decimal::new(15, 1)` is 1.5. More than four decimal
+    /// places are rounded.
+    pub fn new(num: i64, scale: u32) -> Decimal {
+        let num = i128::from(num);
+        let v = if scale <= PLACES {
+            num * 10_i128.pow(PLACES - scale)
+        } else {
+            div_round(num, 10_i128.pow(scale - PLACES))
+        };
+        saturate(v)
+    }
+
+    pub fn from_f64(x: f64) -> Decimal {
+        Decimal((x * SCALE as f64).round() as i64)
+    }
+
+    pub fn to_f64(self) -> f64 {
+        self.0 as f64 / SCALE as f64
+    }
+
+    /// Rounds half away from zero to the given number of decimal places.
+    pub fn round(self, places: u32) -> Decimal {
+        if places >= PLACES {
+            return self;
+        }
+        let d = 10_i128.pow(PLACES - places);
+        saturate(div_round(i128::from(self.0), d) * d)
+    }
+
+    /// Returns the amount for the given number of minutes, `self` being the amount per hour.
+    pub fn per_hour(self, minutes: i64) -> Decimal {
+        self.mul_div(minutes, 60)
+    }
+
+    /// Returns `self * num / den`, rounded once.
+    pub fn mul_div(self, num: i64, den: i64) -> Decimal {
+        saturate(div_round(
+            i128::from(self.0) * i128::from(num),
+            i128::from(den),
+        ))
+    }
+
+    pub fn is_zero(self) -> bool {
+        self.0 == 0
+    }
+}
+
+impl Add for Decimal {
+    type Output = Decimal;
+
+    fn add(self, other: Decimal) -> Decimal {
+        Decimal(self.0 + other.0)
+    }
+}
+
+impl AddAssign for Decimal {
+    fn add_assign(&mut self, other: Decimal) {
+        self.0 += other.0
+    }
+}
+
+impl Sub for Decimal {
+    type Output = Decimal;
+
+    fn sub(self, other: Decimal) -> Decimal {
+        Decimal(self.0 - other.0)
+    }
+}
+
+impl Neg for Decimal {
+    type Output = Decimal;
+
+    fn neg(self) -> Decimal {
+        Decimal(-self.0)
+    }
+}
+
+impl Mul for Decimal {
+    type Output = Decimal;
+
+    fn mul(self, other: Decimal) -> Decimal {
+        saturate(div_round(
+            i128::from(self.0) * i128::from(other.0),
+            i128::from(SCALE),
+        ))
+    }
+}
+
+impl Sum for Decimal {
+    fn sum<I: Iterator<Item = Decimal>>(iter: I) -> Decimal {
+        iter.fold(Decimal::ZERO, Add::add)
+    }
+}
+
+impl FromStr for Decimal {
+    type Err = String;
+
+    /// Parses a decimal number without exponent, e.g. `-12.5`.
+    fn from_str(s: &str) -> Result<Decimal, String> {
+        let err = || format!("Invalid decimal number: {}", s);
+        let t = s.trim();
+        let (negative, t) = match t.strip_prefix('-') {
+            Some(t) => (true, t),
+            None => (false, t),
+        };
+        let (int, frac) = match t.find('.') {
+            Some(i) => (&t[..i], &t[i + 1..]),
+            None => (t, ""),
+        };
+        let digits = |x: &str| x.bytes().all(|c| c.is_ascii_digit());
+        if (int.is_empty() && frac.is_empty()) || !digits(int) || !digits(frac) {
+            return Err(err());
+        }
+
+        let num = format!("{}{}", int, frac)
+            .parse::<i64>()
+            .map_err(|_| err())?;
+        let d = Decimal::new(num, frac.len() as u32);
+        Ok(if negative { -d } else { d })
+    }
+}
+
+impl fmt::Display for Decimal {
+    /// Prints the given number of decimal places (e.g. `{:.2}`), without a precision trailing
+    /// zeros are omitted. Width and alignment are honored.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let value = match f.precision() {
+            Some(p) => self.round(p as u32),
+            None => *self,
+        };
+        let abs = i128::from(value.0).abs();
+        let int = abs / i128::from(SCALE);
+        let mut frac = format!("{:04}", abs % i128::from(SCALE));
+        match f.precision() {
+            Some(p) if p <= PLACES as usize => frac.truncate(p),
+            Some(p) => frac.push_str(&"0".repeat(p - PLACES as usize)),
+            None => frac = frac.trim_end_matches('0').to_string(),
+        }
+        let s = if frac.is_empty() {
+            int.to_string()
+        } else {
+            format!("{}.{}", int, frac)
+        };
+        f.pad_integral(value.0 >= 0, "", &s)
+    }
+}
+
+impl Serialize for Decimal {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+struct DecimalVisitor;
+
+impl<'de> Visitor<'de> for DecimalVisitor {
+    type Value = Decimal;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a decimal number as string or number")
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Decimal, E> {
+        v.parse().map_err(E::custom)
+    }
+
+    fn visit_f64<E: de::Error>(self, v: f64) -> Result<Decimal, E> {
+        Ok(Decimal::from_f64(v))
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<Decimal, E> {
+        Ok(Decimal::new(v, 0))
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<Decimal, E> {
+        Ok(Decimal::new(i64::try_from(v).unwrap_or(i64::MAX), 0))
+    }
+}
+
+impl<'de> Deserialize<'de> for Decimal {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Decimal, D::Error> {
+        deserializer.deserialize_any(DecimalVisitor)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_decimal_from_str() {
+        assert_eq!(Ok(Decimal::new(15, 1)), "1.5".parse());
+        assert_eq!(Ok(Decimal::new(-8050, 2)), "-80.50".parse());
+        assert_eq!(Ok(Decimal::new(5, 1)), ".5".parse());
+        assert_eq!(Ok(Decimal::new(2, 0)), "2.".parse());
+        assert_eq!(Ok(Decimal::new(1, 4)), "0.00005".parse());
+        assert!("1e3".parse::<Decimal>().is_err());
+        assert!(".".parse::<Decimal>().is_err());
+    }
+
+    #[test]
+    fn test_decimal_display() {
+        let d = Decimal::new(123_456, 4);
+        assert_eq!("12.3456", d.to_string());
+        assert_eq!("12.35", format!("{:.2}", d));
+        assert_eq!("12", format!("{:.0}", d));
+        assert_eq!("   12.3", format!("{: >7.1}", d));
+        assert_eq!("-0.50", format!("{:.2}", Decimal::new(-5, 1)));
+        assert_eq!("1", Decimal::ONE.to_string());
+        assert_eq!("0.00", format!("{:.2}", Decimal::new(-4, 3)));
+    }
+
+    #[test]
+    fn test_decimal_arithmetic() {
+        // 0.1 summed up does not drift
+        let sum: Decimal = (0..1000).map(|_| Decimal::new(1, 1)).sum();
+        assert_eq!(Decimal::new(100, 0), sum);
+        assert_eq!(
+            Decimal::new(12, 1),
+            Decimal::new(8, 1) * Decimal::new(15, 1)
+        );
+        // 80/h for 50min
+        assert_eq!(Decimal::new(666_667, 4), Decimal::new(80, 0).per_hour(50));
+        assert_eq!(
+            Decimal::new(6667, 2),
+            Decimal::new(80, 0).per_hour(50).round(2)
+        );
+        assert_eq!(Decimal::new(-1, 2), Decimal::new(-5, 3).round(2));
+        assert_eq!(Decimal::new(333_333, 4), Decimal::new(100, 0).mul_div(1, 3));
+    }
+
+    #[test]
+    fn test_decimal_overflow() {
+        let max = Decimal(i64::MAX);
+        assert_eq!(max, Decimal::new(i64::MAX, 0));
+        assert_eq!(Decimal(i64::MIN), Decimal::new(-i64::MAX, 0));
+        assert_eq!(max, max * Decimal::new(2, 0));
+        assert_eq!(Decimal(i64::MIN), max * Decimal::new(-2, 0));
+        assert_eq!(max, Decimal::new(1_000_000, 0).per_hour(i64::MAX));
+    }
+
+    #[test]
+    fn test_decimal_serde() {
+        let d: Decimal = serde_json::from_str("33.33000183105469").unwrap();
+        assert_eq!(Decimal::new(3333, 2), d);
+        let d: Decimal = serde_json::from_str("50").unwrap();
+        assert_eq!(r#""50""#, serde_json::to_string(&d).unwrap());
+        assert_eq!(
+            r#""80.5""#,
+            serde_json::to_string(&Decimal::new(805, 1)).unwrap()
+        );
+        let d: Decimal = serde_json::from_str(r#""-0.0001""#).unwrap();
+        assert_eq!(Decimal::new(-1, 4), d);
+        assert!(serde_json::from_str::<Decimal>(r#""1e3""#).is_err());
+    }
+}
@@ -0,0 +1,366 @@
+use crate::Error;
+use chrono::{Datelike, Duration, NaiveDate};
+use serde::Deserialize;
+use std::fs;
+use std::path::Path;
+
+/// When a holiday takes place.
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub enum Rule {
+    /// Every year at the given month and day.
+    Fixed { month: u32, day: u32 },
+    /// Only at the given date.
+    Once(NaiveDate),
+    /// Every year at the month and day of the `from` date, starting with that date and ending
+    /// with the `until` date, if any.
+    Yearly {
+        from: NaiveDate,
+        until: Option<NaiveDate>,
+    },
+    /// Every year, the given number of days relative to Easter Sunday.
+    Easter(i64),
+}
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct Holiday {
+    pub name: String,
+    pub rule: Rule,
+    /// The regions the holiday applies to, all regions if empty.
+    pub regions: Vec<String>,
+}
+
+/// A set of public holidays, loaded from a toml or ics file.
+#[derive(PartialEq, Eq, Debug, Clone, Default)]
+pub struct Calendar {
+    holidays: Vec<Holiday>,
+}
+
+/// A holiday as written in the toml file, either `date` or `easter` has to be set.
+#[derive(Deserialize)]
+struct TomlHoliday {
+    name: String,
+    date: Option<String>,
+    easter: Option<i64>,
+    #[serde(default)]
+    regions: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct TomlCalendar {
+    #[serde(default)]
+    holiday: Vec<TomlHoliday>,
+}
+
+/// Returns Easter Sunday of the given year (Gregorian calendar).
+pub fn easter(year: i32) -> NaiveDate {
+    let a = year % 19;
+    let b = year / 100;
+    let c = year % 100;
+    let d = b / 4;
+    let e = b % 4;
+    let f = (b + 8) / 25;
+    let g = (b - f + 1) / 3;
+    let h = (19 * a + b - d - g + 15) % 30;
+    let i = c / 4;
+    let k = c % 4;
+    let l = (32 + 2 * e + 2 * i - h - k) % 7;
+    let m = (a + 11 * h + 22 * l) / 451;
+    let month = (h + l - 7 * m + 114) / 31;
+    let day = (h + l - 7 * m + 114) % 31 + 1;
+    NaiveDate::from_ymd(year, month as u32, day as u32)
+}
+
+/// Parses `MM-DD` as a fixed and `YYYY-MM-DD` as a one-off date.
+fn parse_rule_date(s: &str) -> Option<Rule> {
+    let nums = s
+        .split('-')
+        .map(|x| x.trim().parse::<u32>().ok())
+        .collect::<Option<Vec<_>>>()?;
+    match nums[..] {
+        [month, day] => {
+            // validate against a leap year, so 02-29 is accepted
+            NaiveDate::from_ymd_opt(2000, month, day)?;
+            Some(Rule::Fixed { month, day })
+        }
+        [year, month, day] => NaiveDate::from_ymd_opt(year as i32, month, day).map(Rule::Once),
+        _ => None,
+    }
+}
+
+/// Parses the `RRULE` of an event starting at the given date. Only yearly rules at the date
+/// of the start are supported, optionally ending after a `COUNT` of years or at an `UNTIL` date.
+fn parse_rrule(rrule: &str, start: NaiveDate) -> Option<Rule> {
+    let mut yearly = false;
+    let mut until = None;
+    for x in rrule.split(';') {
+        let i = x.find('=')?;
+        let value = &x[i + 1..];
+        match &x[..i] {
+            "FREQ" if value == "YEARLY" => yearly = true,
+            "INTERVAL" if value == "1" => (),
+            "WKST" => (),
+            "BYMONTH" if value.parse() == Ok(start.month()) => (),
+            "BYMONTHDAY" if value.parse() == Ok(start.day()) => (),
+            "COUNT" => {
+                let count = value.parse::<i32>().ok().filter(|x| *x > 0)?;
+                until = Some(NaiveDate::from_ymd_opt(start.year() + count - 1, 12, 31)?);
+            }
+            "UNTIL" => {
+                until = Some(NaiveDate::parse_from_str(value.get(..8)?, "%Y%m%d").ok()?);
+            }
+            _ => return None,
+        }
+    }
+    if yearly {
+        Some(Rule::Yearly { from: start, until })
+    } else {
+        None
+    }
+}
+
+impl Rule {
+    fn matches(&self, date: NaiveDate) -> bool {
+        match *self {
+            Rule::Fixed { month, day } => date.month() == month && date.day() == day,
+            Rule::Once(d) => date == d,
+            Rule::Yearly { from, until } => {
+                date >= from
+                    && until.is_none_or(|u| date <= u)
+                    && date.month() == from.month()
+                    && date.day() == from.day()
+            }
+            Rule::Easter(offset) => date == easter(date.year()) + Duration::days(offset),
+        }
+    }
+}
+
+impl Calendar {
+    /// Loads the calendar from the given file, an `.ics` file is read as iCalendar, any other
+    /// file as toml.
+    pub fn from_file(file: &str) -> Result<Calendar, Error> {
+        let content = fs::read_to_string(file)?;
+        let ics = Path::new(file)
+            .extension()
+            .is_some_and(|x| x.eq_ignore_ascii_case("ics"));
+        if ics {
+            Calendar::from_ics(&content)
+        } else {
+            Calendar::from_toml(&content)
+        }
+    }
+
+    /// Reads `[[holiday]]` tables with a `name`, either a `date` (`MM-DD` every year or
+    /// `YYYY-MM-DD` once) or an `easter` offset in days, and optional `regions`.
+    pub fn from_toml(s: &str) -> Result<Calendar, Error> {
+        let raw: TomlCalendar = toml::from_str(s).map_err(|e| Error::Calendar(e.to_string()))?;
+
+        let mut holidays = vec![];
+        for h in raw.holiday {
+            let rule = match (&h.date, h.easter) {
+                (Some(date), None) => parse_rule_date(date).ok_or_else(|| {
+                    Error::Calendar(format!("Invalid date of holiday {}: {}", h.name, date))
+                })?,
+                (None, Some(offset)) => Rule::Easter(offset),
+                _ => {
+                    return Err(Error::Calendar(format!(
+                        "Holiday {} needs either a date or an easter offset",
+                        h.name
+                    )))
+                }
+            };
+            holidays.push(Holiday {
+                name: h.name,
+                rule,
+                regions: h.regions,
+            });
+        }
+        Ok(Calendar { holidays })
+    }
+
+    /// Reads the all-day events (`DTSTART;VALUE=DATE`) of an iCalendar file, events with a
+    /// yearly `RRULE` take place every year from their `DTSTART` on, until its `COUNT` or
+    /// `UNTIL`. Any other `RRULE` is rejected.
+    pub fn from_ics(s: &str) -> Result<Calendar, Error> {
+        // unfold continuation lines first
+        let unfolded = s
+            .replace("\r\n", "\n")
+            .replace("\n ", "")
+            .replace("\n\t", "");
+
+        let mut holidays = vec![];
+        let mut event: Option<(Option<String>, Option<NaiveDate>, Option<String>)> = None;
+        for line in unfolded.lines() {
+            let (key, value) = match line.find(':') {
+                Some(i) => (&line[..i], line[i + 1..].trim()),
+                None => continue,
+            };
+            // strip parameters, e.g. DTSTART;VALUE=DATE
+            let name = key.split(';').next().unwrap_or(key);
+
+            match (name, event.as_mut()) {
+                ("BEGIN", None) if value == "VEVENT" => event = Some((None, None, None)),
+                ("SUMMARY", Some(e)) => e.0 = Some(value.to_string()),
+                ("DTSTART", Some(e)) => {
+                    e.1 = NaiveDate::parse_from_str(value.get(..8).unwrap_or(value), "%Y%m%d").ok()
+                }
+                ("RRULE", Some(e)) => e.2 = Some(value.to_string()),
+                ("END", Some(_)) if value == "VEVENT" => {
+                    let (summary, date, rrule) = event.take().unwrap();
+                    let date = date.ok_or_else(|| {
+                        Error::Calendar(format!(
+                            "Event {} has no valid DTSTART",
+                            summary.as_deref().unwrap_or("-")
+                        ))
+                    })?;
+                    let rule = match rrule {
+                        Some(rrule) => parse_rrule(&rrule, date).ok_or_else(|| {
+                            Error::Calendar(format!(
+                                "Unsupported RRULE of event {}: {}",
+                                summary.as_deref().unwrap_or("-"),
+                                rrule
+                            ))
+                        })?,
+                        None => Rule::Once(date),
+                    };
+                    holidays.push(Holiday {
+                        name: summary.unwrap_or_default(),
+                        rule,
+                        regions: vec![],
+                    });
+                }
+                _ => (),
+            }
+        }
+        Ok(Calendar { holidays })
+    }
+
+    /// Returns the calendar with the holidays which apply to the given region, only the
+    /// holidays for all regions if no region is given.
+    pub fn for_region(mut self, region: Option<&str>) -> Calendar {
+        self.holidays.retain(|h| {
+            h.regions.is_empty()
+                || region.is_some_and(|r| h.regions.iter().any(|x| x.eq_ignore_ascii_case(r)))
+        });
+        self
+    }
+
+    /// Returns the holiday at the given date, if any.
+    pub fn holiday(&self, date: NaiveDate) -> Option<&Holiday> {
+        self.holidays.iter().find(|h| h.rule.matches(date))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_easter() {
+        assert_eq!(NaiveDate::from_ymd(2017, 4, 16), easter(2017));
+        assert_eq!(NaiveDate::from_ymd(2024, 3, 31), easter(2024));
+        assert_eq!(NaiveDate::from_ymd(2025, 4, 20), easter(2025));
+    }
+
+    #[test]
+    fn test_calendar_from_toml() {
+        let s = r#"
+            [[holiday]]
+            name = "New Year"
+            date = "01-01"
+
+            [[holiday]]
+            name = "Good Friday"
+            easter = -2
+
+            [[holiday]]
+            name = "Reformation Day"
+            date = "10-31"
+            regions = ["BB", "sn"]
+
+            [[holiday]]
+            name = "Reformation Anniversary"
+            date = "2017-10-31"
+        "#;
+        let cal = Calendar::from_toml(s).unwrap();
+
+        let name = |c: &Calendar, y, m, d| {
+            c.holiday(NaiveDate::from_ymd(y, m, d))
+                .map(|h| h.name.clone())
+        };
+        assert_eq!(Some("New Year".to_string()), name(&cal, 2030, 1, 1));
+        assert_eq!(Some("Good Friday".to_string()), name(&cal, 2017, 4, 14));
+        assert_eq!(None, name(&cal, 2017, 4, 15));
+
+        let by = cal.clone().for_region(Some("by"));
+        assert_eq!(None, name(&by, 2018, 10, 31));
+        assert!(name(&by, 2017, 10, 31).is_some());
+        let bb = cal.for_region(Some("bb"));
+        assert_eq!(Some("Reformation Day".to_string()), name(&bb, 2018, 10, 31));
+
+        assert!(Calendar::from_toml("[[holiday]]\nname = \"x\"").is_err());
+        assert!(Calendar::from_toml("[[holiday]]\nname = \"x\"\ndate = \"13-01\"").is_err());
+    }
+
+    #[test]
+    fn test_calendar_from_ics() {
+        let s = "BEGIN:VCALENDAR\r\n\
+                 BEGIN:VEVENT\r\n\
+                 SUMMARY:Christmas\r\n\
+                 DTSTART;VALUE=DATE:20161225\r\n\
+                 RRULE:FREQ=YEARLY\r\n\
+                 END:VEVENT\r\n\
+                 BEGIN:VEVENT\r\n\
+                 SUMMARY:Unity Day\r\n\
+                 DTSTART;VALUE=DATE:20191003\r\n\
+                 RRULE:FREQ=YEARLY\r\n\
+                 END:VEVENT\r\n\
+                 BEGIN:VEVENT\r\n\
+                 SUMMARY:Repentance Day\r\n\
+                 DTSTART;VALUE=DATE:20161116\r\n\
+                 RRULE:FREQ=YEARLY;COUNT=2\r\n\
+                 END:VEVENT\r\n\
+                 BEGIN:VEVENT\r\n\
+                 SUMMARY:Boxing Day\r\n\
+                 DTSTART;VALUE=DATE:20161226\r\n\
+                 RRULE:FREQ=YEARLY;BYMONTH=12;BYMONTHDAY=26;UNTIL=20181226T000000Z\r\n\
+                 END:VEVENT\r\n\
+                 BEGIN:VEVENT\r\n\
+                 SUMMARY:Company\r\n  day\r\n\
+                 DTSTART;VALUE=DATE:20170602\r\n\
+                 END:VEVENT\r\n\
+                 END:VCALENDAR\r\n";
+        let cal = Calendar::from_ics(s).unwrap();
+
+        let h = cal.holiday(NaiveDate::from_ymd(2020, 12, 25)).unwrap();
+        assert_eq!("Christmas", h.name);
+        let h = cal.holiday(NaiveDate::from_ymd(2017, 6, 2)).unwrap();
+        assert_eq!("Company day", h.name);
+        assert!(cal.holiday(NaiveDate::from_ymd(2018, 6, 2)).is_none());
+
+        // yearly events don't take place before their start
+        assert!(cal.holiday(NaiveDate::from_ymd(2016, 12, 24)).is_none());
+        assert!(cal.holiday(NaiveDate::from_ymd(2015, 12, 25)).is_none());
+        assert!(cal.holiday(NaiveDate::from_ymd(2017, 10, 3)).is_none());
+        let h = cal.holiday(NaiveDate::from_ymd(2019, 10, 3)).unwrap();
+        assert_eq!("Unity Day", h.name);
+        assert!(cal.holiday(NaiveDate::from_ymd(2024, 10, 3)).is_some());
+
+        // discontinued after a count of years or at a date
+        assert!(cal.holiday(NaiveDate::from_ymd(2017, 11, 16)).is_some());
+        assert!(cal.holiday(NaiveDate::from_ymd(2018, 11, 16)).is_none());
+        assert!(cal.holiday(NaiveDate::from_ymd(2018, 12, 26)).is_some());
+        assert!(cal.holiday(NaiveDate::from_ymd(2019, 12, 26)).is_none());
+
+        let event = |rrule: &str| {
+            format!(
+                "BEGIN:VEVENT\nSUMMARY:x\nDTSTART;VALUE=DATE:20161225\nRRULE:{}\nEND:VEVENT",
+                rrule
+            )
+        };
+        assert!(Calendar::from_ics(&event("FREQ=YEARLY;INTERVAL=1")).is_ok());
+        assert!(Calendar::from_ics(&event("FREQ=WEEKLY")).is_err());
+        assert!(Calendar::from_ics(&event("FREQ=YEARLY;INTERVAL=2")).is_err());
+        assert!(Calendar::from_ics(&event("FREQ=YEARLY;BYMONTH=11;BYDAY=4TH")).is_err());
+        assert!(Calendar::from_ics(&event("FREQ=YEARLY;UNTIL=2018")).is_err());
+    }
+}
@@ -1,7 +1,8 @@
-use chrono::{NaiveDate, NaiveTime};
+use chrono::{Datelike, Duration, NaiveDate, NaiveTime};
 use nom::*;
 
-use crate::data::{Day, Part};
+use crate::storage::data::{Absence, Day, Part};
+use crate::storage::decimal::Decimal;
 
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub enum ParserError {
@@ -36,6 +37,11 @@ named!(
     )
 );
 
+named!(
+    iso_week<(u16, u32)>,
+    do_parse!(y: number >> tag!("-W") >> w: number >> ((y, u32::from(w))))
+);
+
 named!(
     time<NaiveTime>,
     do_parse!(h: number >> tag!(":") >> m: number >> (NaiveTime::from_hms(h as u32, m as u32, 0)))
@@ -51,7 +57,7 @@ named!(
 );
 
 named!(
-    unsigned_float<f32>,
+    unsigned_decimal<Decimal>,
     map_res!(
         map_res!(
             recognize!(alt_complete!(
@@ -64,8 +70,35 @@ named!(
 );
 
 named!(
-    factor<f32>,
-    do_parse!(tag!("-") >> f: unsigned_float >> (f))
+    factor<Decimal>,
+    do_parse!(tag!("-") >> f: unsigned_decimal >> (f))
+);
+
+fn is_project_char(c: u8) -> bool {
+    is_alphanumeric(c) || c == b'-' || c == b'_' || c == b'.'
+}
+
+named!(
+    project<String>,
+    do_parse!(
+        tag!("@")
+            >> p: map_res!(take_while1!(is_project_char), std::str::from_utf8)
+            >> (p.to_string())
+    )
+);
+
+named!(
+    absence<String>,
+    do_parse!(
+        tag!("!")
+            >> a: map_res!(take_while1!(is_project_char), std::str::from_utf8)
+            >> (a.to_string())
+    )
+);
+
+named!(
+    stop<(NaiveTime, bool)>,
+    do_parse!(t: time >> n: opt!(complete!(tag!("+1"))) >> ((t, n.is_some())))
 );
 
 named!(
@@ -73,12 +106,17 @@ named!(
     do_parse!(
         start: time
             >> tag!("-")
-            >> stop: time
+            >> stop: opt!(complete!(stop))
             >> factor: opt!(complete!(factor))
+            >> project: opt!(complete!(project))
             >> (Part {
                 start,
-                stop: Some(stop),
-                factor
+                stop: stop.map(|(t, _)| t),
+                factor,
+                project,
+                next_day: stop.is_some_and(|(_, n)| n),
+                start_offset: None,
+                stop_offset: None
             })
     )
 );
@@ -95,13 +133,17 @@ named!(
     do_parse!(
         d: date
             >> multispace
-            >> p: parts
+            >> a: opt!(complete!(absence))
+            >> a: expr_res!(a.map(|x| x.parse::<Absence>()).transpose())
+            >> opt!(complete!(multispace))
+            >> p: opt!(complete!(parts))
             >> opt!(complete!(multispace))
             >> c: opt!(complete!(comment))
             >> (Day {
                 date: d,
-                parts: p,
-                comment: c
+                parts: p.unwrap_or_default(),
+                comment: c,
+                absence: a
             })
     )
 );
@@ -128,6 +170,14 @@ pub fn parse_line(line: &str) -> Result<Day, ParserError> {
     }
 }
 
+/// Parses an ISO week, e.g. `2025-W01`.
+pub fn parse_week(yw: &str) -> Option<(u16, u32)> {
+    match iso_week(yw.as_bytes()) {
+        IResult::Done(_, o) => Some(o),
+        _ => None,
+    }
+}
+
 pub fn parse_date(ymd: &str) -> Option<NaiveDate> {
     match date(ymd.as_bytes()) {
         IResult::Done(_, o) => Some(o),
@@ -142,6 +192,57 @@ pub fn parse_time(t: &str) -> Option<NaiveTime> {
     }
 }
 
+/// Returns the first and last day of the month of the given date.
+fn month_of(date: NaiveDate) -> (NaiveDate, NaiveDate) {
+    let first = NaiveDate::from_ymd(date.year(), date.month(), 1);
+    let next = match date.month() {
+        12 => NaiveDate::from_ymd(date.year() + 1, 1, 1),
+        m => NaiveDate::from_ymd(date.year(), m + 1, 1),
+    };
+    (first, next.pred())
+}
+
+/// Returns the first and last day of the quarter starting with the given month.
+fn quarter_of(year: i32, first_month: u32) -> (NaiveDate, NaiveDate) {
+    let (_, last) = month_of(NaiveDate::from_ymd(year, first_month + 2, 1));
+    (NaiveDate::from_ymd(year, first_month, 1), last)
+}
+
+/// Parses a date (`YYYY-MM-DD`) or a period relative to today, one of `today`, `yesterday`,
+/// `this-week`, `last-week`, `this-month`, `last-month`, `this-quarter`, `last-quarter`,
+/// `this-year` or `last-year`. Returns the first and the last day, which are the same for a
+/// single day.
+pub fn parse_period(s: &str, today: NaiveDate) -> Option<(NaiveDate, NaiveDate)> {
+    let monday = today - Duration::days(i64::from(today.weekday().num_days_from_monday()));
+    let quarter = (today.month() - 1) / 3 * 3 + 1;
+    let year = |y| (NaiveDate::from_ymd(y, 1, 1), NaiveDate::from_ymd(y, 12, 31));
+
+    match s {
+        "today" => Some((today, today)),
+        "yesterday" => Some((today.pred(), today.pred())),
+        "this-week" => Some((monday, monday + Duration::days(6))),
+        "last-week" => Some((monday - Duration::days(7), monday.pred())),
+        "this-month" => Some(month_of(today)),
+        "last-month" => Some(month_of(month_of(today).0.pred())),
+        "this-quarter" => Some(quarter_of(today.year(), quarter)),
+        "last-quarter" => Some(match quarter {
+            1 => quarter_of(today.year() - 1, 10),
+            q => quarter_of(today.year(), q - 3),
+        }),
+        "this-year" => Some(year(today.year())),
+        "last-year" => Some(year(today.year() - 1)),
+        _ => parse_date(s).map(|d| (d, d)),
+    }
+}
+
+/// Parses a stop time, followed by `+1` if it is on the next day.
+pub fn parse_stop(t: &str) -> Option<(NaiveTime, bool)> {
+    match stop(t.as_bytes()) {
+        IResult::Done(_, o) => Some(o),
+        _ => None,
+    }
+}
+
 pub fn parse_part(t: &str) -> Option<Part> {
     match part(t.as_bytes()) {
         IResult::Done(_, o) => Some(o),
@@ -151,7 +252,8 @@ pub fn parse_part(t: &str) -> Option<Part> {
 
 #[cfg(test)]
 mod test {
-    use crate::data::{Day, Part};
+    use crate::storage::data::{Absence, Day, Part};
+    use crate::storage::decimal::Decimal;
     use chrono::{NaiveDate, NaiveTime};
     use nom::IResult;
 
@@ -198,9 +300,16 @@ mod test {
     fn test_parse_factor() {
         assert_eq!(
             super::factor("-1.0".as_bytes()),
-            IResult::Done(&b""[..], 1.0)
+            IResult::Done(&b""[..], Decimal::ONE)
+        );
+        assert_eq!(
+            super::factor("-1".as_bytes()),
+            IResult::Done(&b""[..], Decimal::ONE)
+        );
+        assert_eq!(
+            super::factor("-1.25".as_bytes()),
+            IResult::Done(&b""[..], Decimal::new(125, 2))
         );
-        assert_eq!(super::factor("-1".as_bytes()), IResult::Done(&b""[..], 1.0));
     }
 
     #[test]
@@ -208,7 +317,11 @@ mod test {
         let exp_part = Part {
             start: NaiveTime::from_hms(8, 0, 0),
             stop: Some(NaiveTime::from_hms(11, 30, 0)),
-            factor: Some(1.0),
+            factor: Some(Decimal::ONE),
+            project: None,
+            next_day: false,
+            start_offset: None,
+            stop_offset: None,
         };
         let r = super::part("08:00-11:30-1".as_bytes());
         assert_eq!(r, IResult::Done(&b""[..], exp_part));
@@ -220,22 +333,96 @@ mod test {
             start: NaiveTime::from_hms(8, 0, 0),
             stop: Some(NaiveTime::from_hms(11, 30, 0)),
             factor: None,
+            project: None,
+            next_day: false,
+            start_offset: None,
+            stop_offset: None,
         };
         let r = super::part("08:00-11:30".as_bytes());
         assert_eq!(r, IResult::Done(&b""[..], exp_part));
     }
 
+    #[test]
+    fn test_parse_project() {
+        assert_eq!(
+            super::project("@acme-corp_1 ".as_bytes()),
+            IResult::Done(&b" "[..], "acme-corp_1".to_string())
+        );
+    }
+
+    #[test]
+    fn test_parse_part_with_project() {
+        let exp_part = Part {
+            start: NaiveTime::from_hms(8, 0, 0),
+            stop: Some(NaiveTime::from_hms(11, 30, 0)),
+            factor: None,
+            project: Some("acme".to_string()),
+            next_day: false,
+            start_offset: None,
+            stop_offset: None,
+        };
+        let r = super::part("08:00-11:30@acme".as_bytes());
+        assert_eq!(r, IResult::Done(&b""[..], exp_part));
+
+        let exp_part = Part {
+            start: NaiveTime::from_hms(8, 0, 0),
+            stop: Some(NaiveTime::from_hms(11, 30, 0)),
+            factor: Some(Decimal::new(15, 1)),
+            project: Some("acme".to_string()),
+            next_day: false,
+            start_offset: None,
+            stop_offset: None,
+        };
+        let r = super::part("08:00-11:30-1.5@acme".as_bytes());
+        assert_eq!(r, IResult::Done(&b""[..], exp_part));
+    }
+
+    #[test]
+    fn test_parse_part_open() {
+        let exp_part = Part {
+            start: NaiveTime::from_hms(8, 0, 0),
+            stop: None,
+            factor: Some(Decimal::new(15, 1)),
+            project: None,
+            next_day: false,
+            start_offset: None,
+            stop_offset: None,
+        };
+        let r = super::part("08:00--1.5".as_bytes());
+        assert_eq!(r, IResult::Done(&b""[..], exp_part));
+
+        let exp_part = Part {
+            start: NaiveTime::from_hms(8, 0, 0),
+            stop: None,
+            factor: None,
+            project: None,
+            next_day: false,
+            start_offset: None,
+            stop_offset: None,
+        };
+        let r = super::part("08:00-".as_bytes());
+        assert_eq!(r, IResult::Done(&b""[..], exp_part));
+    }
+
     #[test]
     fn test_parse_parts() {
         let p1 = Part {
             start: NaiveTime::from_hms(8, 0, 0),
             stop: Some(NaiveTime::from_hms(11, 30, 0)),
-            factor: Some(2.0),
+            factor: Some(Decimal::new(2, 0)),
+            project: None,
+            next_day: false,
+            start_offset: None,
+            stop_offset: None,
         };
         let p2 = Part {
             start: NaiveTime::from_hms(12, 30, 0),
             stop: Some(NaiveTime::from_hms(17, 59, 0)),
             factor: None,
+            project: None,
+            next_day: false,
+            start_offset: None,
+            stop_offset: None,
         };
 
         let exp_parts = vec![p1, p2];
@@ -249,20 +436,29 @@ mod test {
         let p1 = Part {
             start: NaiveTime::from_hms(8, 0, 0),
             stop: Some(NaiveTime::from_hms(11, 30, 0)),
-            factor: Some(2.0),
+            factor: Some(Decimal::new(2, 0)),
+            project: None,
+            next_day: false,
+            start_offset: None,
+            stop_offset: None,
         };
         let p2 = Part {
             start: NaiveTime::from_hms(12, 30, 0),
             stop: Some(NaiveTime::from_hms(17, 59, 0)),
             factor: None,
+            project: None,
+            next_day: false,
+            start_offset: None,
+            stop_offset: None,
         };
 
         let exp_parts = vec![p1, p2];
 
         let exp_day = Day {
-            date: NaiveDate::from_ymd(2017, 03, 20),
+            date: NaiveDate::from_ymd(2017, 3, 20),
             parts: exp_parts,
             comment: Some("foo bar".to_string()),
+            absence: None,
         };
         let r = super::day("2017-03-20    08:00-11:30-2 12:30-17:59  #foo bar".as_bytes());
         println!("{:?}", r);
@@ -275,22 +471,76 @@ mod test {
             start: NaiveTime::from_hms(8, 0, 0),
             stop: Some(NaiveTime::from_hms(11, 30, 0)),
             factor: None,
+            project: None,
+            next_day: false,
+            start_offset: None,
+            stop_offset: None,
         };
         let p2 = Part {
             start: NaiveTime::from_hms(12, 30, 0),
             stop: Some(NaiveTime::from_hms(17, 59, 0)),
             factor: None,
+            project: None,
+            next_day: false,
+            start_offset: None,
+            stop_offset: None,
         };
 
         let exp_parts = vec![p1, p2];
 
         let exp_day = Day {
-            date: NaiveDate::from_ymd(2017, 03, 20),
+            date: NaiveDate::from_ymd(2017, 3, 20),
             parts: exp_parts,
             comment: None,
+            absence: None,
         };
         let r = super::day("2017-03-20    08:00-11:30  12:30-17:59".as_bytes());
         println!("{:?}", r);
         assert_eq!(r, IResult::Done(&b""[..], exp_day));
     }
+
+    #[test]
+    fn test_parse_week() {
+        assert_eq!(Some((2025, 1)), super::parse_week("2025-W01"));
+        assert_eq!(Some((2020, 53)), super::parse_week("2020-W53"));
+        assert_eq!(None, super::parse_week("35"));
+    }
+
+    #[test]
+    fn test_parse_period() {
+        let d = |y, m, d| NaiveDate::from_ymd(y, m, d);
+        let today = d(2024, 1, 17);
+        let period = |s| super::parse_period(s, today);
+        assert_eq!(Some((d(2024, 3, 16), d(2024, 3, 16))), period("2024-03-16"));
+        assert_eq!(Some((d(2024, 1, 16), d(2024, 1, 16))), period("yesterday"));
+        assert_eq!(Some((d(2024, 1, 8), d(2024, 1, 14))), period("last-week"));
+        assert_eq!(
+            Some((d(2023, 12, 1), d(2023, 12, 31))),
+            period("last-month")
+        );
+        assert_eq!(
+            Some((d(2024, 1, 1), d(2024, 3, 31))),
+            period("this-quarter")
+        );
+        assert_eq!(
+            Some((d(2023, 10, 1), d(2023, 12, 31))),
+            period("last-quarter")
+        );
+        assert_eq!(Some((d(2023, 1, 1), d(2023, 12, 31))), period("last-year"));
+        assert_eq!(None, period("next-week"));
+    }
+
+    #[test]
+    fn test_parse_line_absence() {
+        let d = super::parse_line("2017-03-20   !vacation   # summer").unwrap();
+        assert_eq!(Some(Absence::Vacation), d.absence);
+        assert!(d.parts.is_empty());
+        assert_eq!(Some("summer".to_string()), d.comment);
+
+        let d = super::parse_line("2017-03-20   !comp-time  08:00-10:00").unwrap();
+        assert_eq!(Some(Absence::CompTime), d.absence);
+        assert_eq!(1, d.parts.len());
+
+        assert!(super::parse_line("2017-03-20   !party").is_err());
+    }
 }
@@ -0,0 +1,94 @@
+use fs2::FileExt;
+use std::collections::hash_map::DefaultHasher;
+use std::fs::{self, File, OpenOptions};
+use std::hash::Hasher;
+use std::io;
+use std::path::PathBuf;
+
+/// An exclusive advisory lock on a store, released when dropped.
+///
+/// The lock is held on a separate `<store>.lock` file since saving replaces the store file
+/// itself.
+#[derive(Debug)]
+pub struct StoreLock {
+    file: File,
+}
+
+fn lock_path(store_file: &str) -> PathBuf {
+    PathBuf::from(format!("{}.lock", store_file))
+}
+
+impl StoreLock {
+    /// Acquires the lock for the given store, blocks until the lock is available.
+    pub fn acquire(store_file: &str) -> io::Result<StoreLock> {
+        let file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(false)
+            .open(lock_path(store_file))?;
+
+        file.lock_exclusive()?;
+        Ok(StoreLock { file })
+    }
+
+    /// Acquires the lock for the given store if it is available right away, `None` if it is
+    /// held by another process.
+    pub fn try_acquire(store_file: &str) -> io::Result<Option<StoreLock>> {
+        let file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(false)
+            .open(lock_path(store_file))?;
+
+        match file.try_lock_exclusive() {
+            Ok(()) => Ok(Some(StoreLock { file })),
+            Err(e) if e.raw_os_error() == fs2::lock_contended_error().raw_os_error() => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+impl Drop for StoreLock {
+    fn drop(&mut self) {
+        let _ = self.file.unlock();
+    }
+}
+
+/// Returns a fingerprint of the store's content which allows to detect whether the store has
+/// been changed on disk, e.g. by another reti process.
+pub fn fingerprint(store_file: &str) -> io::Result<u64> {
+    let content = fs::read(store_file)?;
+    let mut hasher = DefaultHasher::new();
+    hasher.write(&content);
+    Ok(hasher.finish())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_lock_exclusive() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("store.json");
+        let file = path.to_str().unwrap();
+
+        let lock = StoreLock::acquire(file).unwrap();
+        assert!(StoreLock::try_acquire(file).unwrap().is_none());
+        drop(lock);
+        assert!(StoreLock::try_acquire(file).unwrap().is_some());
+    }
+
+    #[test]
+    fn test_fingerprint() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("store.json");
+        let file = path.to_str().unwrap();
+
+        fs::write(&path, "{}").unwrap();
+        let a = fingerprint(file).unwrap();
+        assert_eq!(a, fingerprint(file).unwrap());
+        fs::write(&path, "{ }").unwrap();
+        assert_ne!(a, fingerprint(file).unwrap());
+    }
+}
@@ -0,0 +1,129 @@
+use crate::storage::data::Part;
+use crate::Error;
+use chrono::{
+    DateTime, Duration, FixedOffset, Local, LocalResult, NaiveDate, NaiveDateTime, Offset,
+    TimeZone, Utc,
+};
+use chrono_tz::Tz;
+use std::env;
+use std::str::FromStr;
+
+/// The time zone in which times are recorded, either a named (IANA) zone, e.g.
+/// `Europe/Berlin`, or the local zone of the system.
+#[derive(PartialEq, Debug, Clone, Copy, Default)]
+pub enum Zone {
+    #[default]
+    Local,
+    Named(Tz),
+}
+
+impl FromStr for Zone {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Zone, Error> {
+        // TZ may be prefixed with ':', e.g. TZ=:Europe/Berlin
+        let s = s.trim().trim_start_matches(':');
+        s.parse::<Tz>()
+            .map(Zone::Named)
+            .map_err(|_| Error::UnknownTimeZone(s.to_string()))
+    }
+}
+
+fn fix<T: TimeZone>(r: LocalResult<DateTime<T>>, earliest: bool) -> Option<FixedOffset> {
+    match r {
+        LocalResult::Single(x) => Some(x.offset().fix()),
+        LocalResult::Ambiguous(a, b) => Some(if earliest { a } else { b }.offset().fix()),
+        LocalResult::None => None,
+    }
+}
+
+impl Zone {
+    /// Returns the zone named by the `TZ` environment variable, the local zone of the system if
+    /// it is not set or unknown.
+    pub fn from_env() -> Zone {
+        env::var("TZ")
+            .ok()
+            .and_then(|x| x.parse().ok())
+            .unwrap_or_default()
+    }
+
+    /// Returns the current time in this zone.
+    pub fn now(&self) -> DateTime<FixedOffset> {
+        match *self {
+            Zone::Local => {
+                let now = Local::now();
+                now.with_timezone(&now.offset().fix())
+            }
+            Zone::Named(tz) => {
+                let now = Utc::now().with_timezone(&tz);
+                now.with_timezone(&now.offset().fix())
+            }
+        }
+    }
+
+    pub fn today(&self) -> NaiveDate {
+        self.now().naive_local().date()
+    }
+
+    /// Returns the offset from UTC at the given wall clock time. If the time occurs twice (the
+    /// clocks are turned back), the offset of the earlier or later instant is returned. Returns
+    /// `None` for a time which does not exist (the clocks are turned forward).
+    pub fn offset(&self, local: &NaiveDateTime, earliest: bool) -> Option<FixedOffset> {
+        match *self {
+            Zone::Local => fix(Local.from_local_datetime(local), earliest),
+            Zone::Named(tz) => fix(tz.from_local_datetime(local), earliest),
+        }
+    }
+
+    /// Sets the missing UTC offsets of the part recorded at the given date. The start is
+    /// resolved to the earlier and the stop to the later instant if the time occurs twice.
+    pub fn resolve(&self, date: NaiveDate, part: &mut Part) {
+        let secs = |x: FixedOffset| x.local_minus_utc();
+        if part.start_offset.is_none() {
+            part.start_offset = self.offset(&date.and_time(part.start), true).map(secs);
+        }
+        if let (None, Some(stop)) = (part.stop_offset, part.stop) {
+            let date = if part.next_day {
+                date + Duration::days(1)
+            } else {
+                date
+            };
+            part.stop_offset = self.offset(&date.and_time(stop), false).map(secs);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::storage::legacy_parser;
+
+    #[test]
+    fn test_zone_from_str() {
+        assert_eq!(
+            Zone::Named(chrono_tz::Europe::Berlin),
+            ":Europe/Berlin".parse().unwrap()
+        );
+        assert!(matches!(
+            "Mars/Olympus".parse::<Zone>(),
+            Err(Error::UnknownTimeZone(_))
+        ));
+    }
+
+    #[test]
+    fn test_zone_resolve_dst() {
+        let zone = Zone::Named(chrono_tz::Europe::Berlin);
+        let worked = |y, m, d, p: &str| {
+            let mut part = legacy_parser::parse_part(p).unwrap();
+            zone.resolve(NaiveDate::from_ymd(y, m, d), &mut part);
+            part.worked().unwrap().num_minutes()
+        };
+        // clocks turned forward at 02:00
+        assert_eq!(120, worked(2021, 3, 28, "01:00-04:00"));
+        assert_eq!(360, worked(2021, 3, 27, "23:00-06:00+1"));
+        // clocks turned back at 03:00
+        assert_eq!(240, worked(2021, 10, 31, "01:00-04:00"));
+        assert_eq!(60, worked(2021, 10, 31, "02:30-02:30"));
+        assert_eq!(180, worked(2021, 11, 1, "01:00-04:00"));
+    }
+}
@@ -3,8 +3,8 @@ use std::io::prelude::*;
 
 #[derive(Debug)]
 pub enum YesNoAnswer {
-    YES,
-    NO,
+    Yes,
+    No,
 }
 
 pub fn yes_no(message: &str, default: YesNoAnswer) -> YesNoAnswer {
@@ -17,8 +17,8 @@ pub fn yes_no(message: &str, default: YesNoAnswer) -> YesNoAnswer {
     io::stdin().read_line(&mut input).unwrap();
 
     match input.trim() {
-        "Yes" | "yes" | "Y" | "y" | "YeS" | "YES" => YesNoAnswer::YES,
-        "No" | "NO" | "n" | "N" => YesNoAnswer::NO,
+        "Yes" | "yes" | "Y" | "y" | "YeS" | "YES" => YesNoAnswer::Yes,
+        "No" | "NO" | "n" | "N" => YesNoAnswer::No,
         _ => default,
     }
 }